@@ -1,56 +1,142 @@
-use std::{cell::UnsafeCell, fmt::Debug, hash::Hash, ops::Range, sync::Arc};
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    ops::Range,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
-/// A read-write block of memory. This the mutable version of `Block`.
-#[derive(Clone)]
-pub(crate) struct Block {
-    data: Arc<UnsafeCell<Vec<u8>>>,
-    range: Range<usize>,
+use super::Releaser;
+
+/// The memory behind a block while it sits in a pool.
+pub(crate) type Memory = Box<[u8]>;
+
+/// The allocation shared by every view of a block.
+///
+/// The memory is only reachable through a `Block`, which is not `Clone` and owns a range that no
+/// other view overlaps, or a `SharedBlock`, which is read-only and can only be made by consuming a
+/// `Block`. So a mutable range never aliases any other view, and once the last view is dropped the
+/// chunk has exclusive access to the memory when releasing it.
+struct Chunk {
+    ptr: NonNull<u8>,
+    len: usize,
+    // the end of the furthest filled byte, shared across splits.
+    used: AtomicUsize,
+    releaser: Option<Releaser>,
 }
 
-impl Block {
-    pub fn new(capacity: usize) -> Self {
+// SAFETY: the chunk owns its memory and views never alias a range mutably, see `Chunk`.
+unsafe impl Send for Chunk {}
+// SAFETY: see above.
+unsafe impl Sync for Chunk {}
+
+impl Chunk {
+    fn new(memory: Memory, releaser: Option<Releaser>) -> Self {
+        let len = memory.len();
+        let ptr = NonNull::new(Box::into_raw(memory).cast::<u8>()).expect("boxed memory");
         Self {
-            data: Arc::new(UnsafeCell::new(vec![0; capacity])),
-            range: 0..capacity,
+            ptr,
+            len,
+            used: AtomicUsize::new(0),
+            releaser,
         }
     }
 
-    pub fn release(&mut self) -> Self {
-        let Self { data, range: _ } = self;
-        let capacity = {
-            let data = unsafe { &mut *data.get() };
-            data.fill(0);
-            data.len()
-        };
+    /// # Safety
+    /// `range` must be within the chunk and must not be mutably borrowed elsewhere.
+    unsafe fn slice(&self, range: &Range<usize>) -> &[u8] {
+        std::slice::from_raw_parts(self.ptr.as_ptr().add(range.start), range.len())
+    }
+
+    /// # Safety
+    /// `range` must be within the chunk and must not be borrowed elsewhere.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn slice_mut(&self, range: &Range<usize>) -> &mut [u8] {
+        std::slice::from_raw_parts_mut(self.ptr.as_ptr().add(range.start), range.len())
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        let memory = std::ptr::slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len);
+        // SAFETY: the pointer and length came from `Box::into_raw` and, as the last view is gone,
+        // nothing else can reach the memory.
+        let memory = unsafe { Box::from_raw(memory) };
+        if let Some(releaser) = self.releaser.take() {
+            releaser.release(memory, *self.used.get_mut());
+        }
+    }
+}
+
+/// A read-write block of memory. This the mutable version of `SharedBlock`.
+///
+/// Splitting hands out disjoint ranges of the same allocation, which is returned to its pool once
+/// every split and shared view of it has been dropped.
+pub(crate) struct Block {
+    chunk: Arc<Chunk>,
+    range: Range<usize>,
+}
+
+impl Block {
+    pub fn new(memory: Memory, releaser: Option<Releaser>) -> Self {
+        let capacity = memory.len();
         Self {
-            data: data.clone(),
+            chunk: Arc::new(Chunk::new(memory, releaser)),
             range: 0..capacity,
         }
     }
 
     pub fn as_slice(&self) -> &[u8] {
-        let data = unsafe { &*self.data.get() };
-        &data[self.range.clone()]
+        // SAFETY: this block is the only view of its range.
+        unsafe { self.chunk.slice(&self.range) }
     }
 
     pub fn as_mut_slice(&mut self) -> &mut [u8] {
-        let data = unsafe { &mut *self.data.get() };
-        &mut data[self.range.clone()]
+        // SAFETY: this block is the only view of its range and is borrowed mutably.
+        unsafe { self.chunk.slice_mut(&self.range) }
     }
 
     pub fn capacity(&self) -> usize {
         self.range.end - self.range.start
     }
 
+    /// Marks the first `len` bytes of this block's range as filled.
+    pub fn mark_used(&self, len: usize) {
+        self.chunk
+            .used
+            .fetch_max(self.range.start + len, Ordering::AcqRel);
+    }
+
+    /// Splits the block into two at the given index. Returns the left part and keeps the right part.
+    ///
+    /// # Panics
+    /// If `index` is larger than the capacity.
     pub fn split_at(&mut self, index: usize) -> Self {
+        assert!(
+            index <= self.capacity(),
+            "split index {} > capacity {}",
+            index,
+            self.capacity()
+        );
         let right = (self.range.start + index)..self.range.end;
         let left = self.range.start..(self.range.start + index);
         self.range = right;
         Self {
-            data: self.data.clone(),
+            chunk: self.chunk.clone(),
             range: left,
         }
     }
+
+    /// Gives up write access to the range so it can be shared.
+    pub fn into_shared(self) -> SharedBlock {
+        SharedBlock {
+            chunk: self.chunk,
+            range: self.range,
+        }
+    }
 }
 
 impl Debug for Block {
@@ -62,45 +148,86 @@ impl Debug for Block {
     }
 }
 
-impl PartialEq for Block {
+/// A read-only view of a block of memory, cheap to clone.
+#[derive(Clone)]
+pub(crate) struct SharedBlock {
+    chunk: Arc<Chunk>,
+    range: Range<usize>,
+}
+
+impl SharedBlock {
+    pub fn as_slice(&self) -> &[u8] {
+        // SAFETY: the range came from a consumed `Block`, so no view can write to it.
+        unsafe { self.chunk.slice(&self.range) }
+    }
+}
+
+impl Debug for SharedBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedBlock")
+            .field("data", &self.as_slice())
+            .field("range", &self.range)
+            .finish()
+    }
+}
+
+impl PartialEq for SharedBlock {
     fn eq(&self, other: &Self) -> bool {
         self.as_slice() == other.as_slice()
     }
 }
 
-impl Eq for Block {}
+impl Eq for SharedBlock {}
 
 #[cfg_attr(coverage_nightly, coverage(off))]
-impl PartialOrd for Block {
+impl PartialOrd for SharedBlock {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
 #[cfg_attr(coverage_nightly, coverage(off))]
-impl Ord for Block {
+impl Ord for SharedBlock {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.as_slice().cmp(other.as_slice())
     }
 }
 
 #[cfg_attr(coverage_nightly, coverage(off))]
-impl Hash for Block {
+impl Hash for SharedBlock {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.as_slice().hash(state)
     }
 }
 
-unsafe impl Send for Block {}
-unsafe impl Sync for Block {}
-
+// These tests exercise the unsafe aliasing model and are meant to also be run under Miri:
+// `cargo +nightly miri test buffer::`
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
+    use crossbeam_channel::{unbounded, Receiver};
+
+    use crate::buffer::{stats::Metrics, Releaser, ReturnPath, ZeroPolicy};
+
     use super::*;
 
+    fn block(data: &[u8], zero: ZeroPolicy) -> (Block, Arc<Receiver<Memory>>, Arc<Metrics>) {
+        let (tx, rx) = unbounded();
+        let rx = Arc::new(rx);
+        let metrics = Arc::new(Metrics::default());
+        metrics.acquired("test", Default::default());
+        let returns = ReturnPath::new(tx, &rx, 1);
+        let releaser = Releaser::new(returns, metrics.clone(), "test", None, zero);
+        let mut block = Block::new(vec![0; data.len()].into_boxed_slice(), Some(releaser));
+        block.as_mut_slice().copy_from_slice(data);
+        block.mark_used(data.len());
+        (block, rx, metrics)
+    }
+
     #[test]
     fn debug() {
-        let mut block = Block::new(10);
+        let mut block = Block::new(vec![0; 10].into_boxed_slice(), None);
         assert_eq!(
             format!("{:?}", block),
             "Block { data: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0], range: 0..10 }"
@@ -115,5 +242,138 @@ mod tests {
             format!("{:?}", block),
             "Block { data: [0, 0, 0, 0, 0, 0], range: 4..10 }"
         );
+        assert_eq!(
+            format!("{:?}", other.into_shared()),
+            "SharedBlock { data: [0, 0, 0, 0], range: 0..4 }"
+        );
+    }
+
+    #[test]
+    fn split_writes_are_disjoint() {
+        let (mut right, rx, _) = block(&[0; 8], ZeroPolicy::Never);
+        let mut left = right.split_at(3);
+        let mut middle = right.split_at(2);
+
+        left.as_mut_slice().fill(1);
+        middle.as_mut_slice().fill(2);
+        right.as_mut_slice().fill(3);
+        let left = left.into_shared();
+        let middle = middle.into_shared();
+
+        assert_eq!(left.as_slice(), &[1, 1, 1]);
+        assert_eq!(middle.as_slice(), &[2, 2]);
+        assert_eq!(right.as_slice(), &[3, 3, 3]);
+
+        drop((left, middle, right));
+        assert_eq!(
+            &*rx.try_recv().expect("released"),
+            &[1, 1, 1, 2, 2, 3, 3, 3]
+        );
+    }
+
+    #[test]
+    fn split_at_edges() {
+        let (mut block, rx, _) = block(&[1, 2, 3], ZeroPolicy::Never);
+        let empty = block.split_at(0);
+        assert!(empty.as_slice().is_empty());
+        let all = block.split_at(3);
+        assert_eq!(all.as_slice(), &[1, 2, 3]);
+        assert!(block.as_slice().is_empty());
+
+        drop((empty, all, block));
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn split_past_capacity() {
+        let mut block = Block::new(vec![0; 4].into_boxed_slice(), None);
+        let _ = block.split_at(5);
+    }
+
+    #[test]
+    fn released_once_after_last_view() {
+        let (mut right, rx, metrics) = block(&[1, 2, 3, 4], ZeroPolicy::Always);
+        let left = right.split_at(2).into_shared();
+        let clone = left.clone();
+
+        drop(right);
+        assert!(rx.try_recv().is_err());
+        drop(left);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(clone.as_slice(), &[1, 2]);
+        drop(clone);
+
+        assert_eq!(&*rx.try_recv().expect("released"), &[0, 0, 0, 0]);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(metrics.in_use(), 0);
+    }
+
+    #[test]
+    fn shared_outlives_owner() {
+        let (mut block, rx, _) = block(&[1, 2, 3, 4], ZeroPolicy::Used);
+        let shared = block.split_at(4).into_shared();
+        drop(block);
+
+        let clones = vec![shared.clone(), shared.clone()];
+        drop(shared);
+        for clone in &clones {
+            assert_eq!(clone.as_slice(), &[1, 2, 3, 4]);
+        }
+        assert!(rx.try_recv().is_err());
+        drop(clones);
+
+        assert_eq!(&*rx.try_recv().expect("released"), &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn concurrent_drops_release_once() {
+        let (mut right, rx, metrics) = block(&[7; 16], ZeroPolicy::Secure);
+        let mut views = vec![];
+        for _ in 0..3 {
+            views.push(right.split_at(4).into_shared());
+        }
+        let views = views
+            .into_iter()
+            .chain([right.into_shared()])
+            .flat_map(|view| [view.clone(), view])
+            .collect::<Vec<_>>();
+
+        let handles = views
+            .into_iter()
+            .map(|view| {
+                std::thread::spawn(move || {
+                    assert_eq!(view.as_slice(), &[7; 4]);
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().expect("join");
+        }
+
+        assert_eq!(&*rx.try_recv().expect("released"), &[0; 16]);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(metrics.in_use(), 0);
+    }
+
+    #[test]
+    fn writes_while_other_half_is_shared() {
+        let (mut right, rx, _) = block(&[0; 4], ZeroPolicy::Never);
+        let left = right.split_at(2).into_shared();
+
+        let handle = std::thread::spawn(move || left.as_slice().to_vec());
+        right.as_mut_slice().copy_from_slice(&[5, 6]);
+        assert_eq!(handle.join().expect("join"), vec![0, 0]);
+        drop(right);
+
+        assert_eq!(&*rx.try_recv().expect("released"), &[0, 0, 5, 6]);
+    }
+
+    #[test]
+    fn without_releaser() {
+        let mut block = Block::new(vec![1, 2, 3].into_boxed_slice(), None);
+        let left = block.split_at(1).into_shared();
+        drop(block);
+        assert_eq!(left.as_slice(), &[1]);
     }
 }
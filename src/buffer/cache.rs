@@ -0,0 +1,233 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+use crossbeam_channel::Sender;
+
+use super::block::Memory;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+type Slot = Mutex<Vec<Memory>>;
+
+thread_local! {
+    // this thread's slot for each pool with a cache, keyed by the cache id. Only the cache keeps
+    // its slots alive, so the blocks in them are freed with the pool rather than the thread.
+    static SLOTS: RefCell<HashMap<u64, Weak<Slot>>> = RefCell::new(HashMap::new());
+}
+
+/// A per-thread cache of released blocks that sits in front of a pool's channel.
+///
+/// Releases go to the releasing thread's slot and are returned to the channel in batches once the
+/// slot grows past `depth`. Slots stay reachable from the pool, so a thread that would otherwise
+/// wait flushes every slot to the channel first and every block in the pool can still be acquired.
+#[derive(Debug)]
+pub(crate) struct ThreadCache {
+    id: u64,
+    depth: usize,
+    waiters: AtomicUsize,
+    slots: Mutex<Vec<Arc<Slot>>>,
+}
+
+impl ThreadCache {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            depth,
+            waiters: AtomicUsize::new(0),
+            slots: Mutex::default(),
+        }
+    }
+
+    /// Runs `f` with this thread's slot, or returns `None` if the thread is shutting down.
+    fn with_slot<T>(&self, f: impl FnOnce(&Slot) -> T) -> Option<T> {
+        SLOTS
+            .try_with(|slots| {
+                let mut slots = slots.borrow_mut();
+                let slot = match slots.get(&self.id).and_then(Weak::upgrade) {
+                    Some(slot) => slot,
+                    None => {
+                        // forget the slots of caches that have been dropped.
+                        slots.retain(|_, slot| slot.strong_count() > 0);
+
+                        let slot = Arc::new(Slot::default());
+                        self.slots.lock().expect("slots lock").push(slot.clone());
+                        slots.insert(self.id, Arc::downgrade(&slot));
+                        slot
+                    }
+                };
+                drop(slots);
+                f(&slot)
+            })
+            .ok()
+    }
+
+    /// Takes a block from this thread's slot.
+    pub fn pop(&self) -> Option<Memory> {
+        self.with_slot(|slot| slot.lock().expect("slot lock").pop())
+            .flatten()
+    }
+
+    /// Puts a released block in this thread's slot, sending a batch to the channel once the slot
+    /// is over its depth. Goes straight to the channel if anyone is waiting for a block.
+    pub fn push(&self, memory: Memory, sender: &Sender<Memory>) -> Result<(), Memory> {
+        let mut memory = Some(memory);
+        self.with_slot(|slot| {
+            let mut slot = slot.lock().expect("slot lock");
+            // checked under the slot lock so a waiter's flush cannot miss this block.
+            if self.waiters.load(Ordering::SeqCst) > 0 {
+                return;
+            }
+
+            slot.extend(memory.take());
+            if slot.len() > self.depth {
+                let keep = self.depth / 2;
+                for memory in slot.drain(keep..) {
+                    send(sender, memory);
+                }
+            }
+        });
+
+        match memory {
+            Some(memory) => Err(memory),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends every cached block, from every thread, to the channel.
+    pub fn flush(&self, sender: &Sender<Memory>) {
+        let mut slots = self.slots.lock().expect("slots lock");
+        for slot in slots.iter() {
+            for memory in slot.lock().expect("slot lock").drain(..) {
+                send(sender, memory);
+            }
+        }
+        // forget the slots of threads that have exited.
+        slots.retain(|slot| Arc::weak_count(slot) > 0);
+    }
+
+    /// Runs `wait` while registered as a waiter, after flushing every cached block to the channel.
+    pub fn waiting<T>(&self, sender: &Sender<Memory>, wait: impl FnOnce() -> T) -> T {
+        self.enter(sender);
+        let result = wait();
+        self.leave();
+        result
+    }
+
+    /// Registers a waiter, releases bypass the cache until it leaves. Flushes every cached block
+    /// to the channel so the waiter can get it.
+    pub fn enter(&self, sender: &Sender<Memory>) {
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        self.flush(sender);
+    }
+
+    pub fn leave(&self) {
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Returns the number of blocks cached across all threads.
+    pub fn cached(&self) -> usize {
+        self.slots
+            .lock()
+            .expect("slots lock")
+            .iter()
+            .map(|slot| slot.lock().expect("slot lock").len())
+            .sum()
+    }
+}
+
+fn send(sender: &Sender<Memory>, memory: Memory) {
+    // the channel holds every block of the pool, so this only fails once the pool is gone.
+    let _ = sender.send(memory);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crossbeam_channel::bounded;
+
+    use super::{ThreadCache, SLOTS};
+
+    #[test]
+    fn batches_returns() {
+        let (tx, rx) = bounded(8);
+        let cache = ThreadCache::new(4);
+
+        for _ in 0..4 {
+            cache.push(vec![0].into_boxed_slice(), &tx).expect("cached");
+        }
+        assert_eq!(cache.cached(), 4);
+        assert!(rx.is_empty());
+
+        cache.push(vec![0].into_boxed_slice(), &tx).expect("cached");
+        assert_eq!(cache.cached(), 2);
+        assert_eq!(rx.len(), 3);
+
+        assert!(cache.pop().is_some());
+        assert_eq!(cache.cached(), 1);
+    }
+
+    #[test]
+    fn flush_steals_from_other_threads() {
+        let (tx, rx) = bounded(8);
+        let cache = std::sync::Arc::new(ThreadCache::new(4));
+
+        {
+            let cache = cache.clone();
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                cache.push(vec![0].into_boxed_slice(), &tx).expect("cached");
+            })
+            .join()
+            .expect("join");
+        }
+        assert!(cache.pop().is_none());
+        assert_eq!(cache.cached(), 1);
+
+        cache.flush(&tx);
+        assert_eq!(cache.cached(), 0);
+        assert_eq!(rx.len(), 1);
+        // only this thread's slot is left.
+        assert_eq!(cache.slots.lock().expect("slots").len(), 1);
+    }
+
+    #[test]
+    fn bypassed_while_waiting() {
+        let (tx, rx) = bounded(8);
+        let cache = ThreadCache::new(4);
+
+        let memory = cache.waiting(&tx, || {
+            cache.push(vec![0].into_boxed_slice(), &tx).unwrap_err()
+        });
+        assert_eq!(&*memory, &[0]);
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn dropped_with_pool() {
+        let (tx, _rx) = bounded(8);
+        let cache = ThreadCache::new(4);
+        cache.push(vec![0].into_boxed_slice(), &tx).expect("cached");
+        let slot = Arc::downgrade(&cache.slots.lock().expect("slots")[0]);
+        let id = cache.id;
+
+        // the cached block goes with the cache, not with this thread.
+        drop(cache);
+        assert!(slot.upgrade().is_none());
+
+        // and the thread forgets the slot once it uses another cache.
+        let cache = ThreadCache::new(4);
+        assert!(cache.pop().is_none());
+        SLOTS.with(|slots| {
+            let slots = slots.borrow();
+            assert!(!slots.contains_key(&id));
+            assert!(slots.contains_key(&cache.id));
+        });
+    }
+}
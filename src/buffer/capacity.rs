@@ -0,0 +1,89 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of blocks a pool should hold, shared by every clone of the pool and its releasers.
+///
+/// Shrinking a pool while its blocks are acquired cannot free them right away, so the blocks still
+/// to be removed are counted as retiring and are freed instead of returned as they are released.
+#[derive(Debug)]
+pub(crate) struct Capacity {
+    total: AtomicUsize,
+    retiring: AtomicUsize,
+}
+
+impl Capacity {
+    pub fn new(total: usize) -> Self {
+        Self {
+            total: AtomicUsize::new(total),
+            retiring: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Acquire)
+    }
+
+    pub fn retiring(&self) -> usize {
+        self.retiring.load(Ordering::Acquire)
+    }
+
+    /// Adds `n` blocks. Returns how many of them have to be allocated, blocks that are still
+    /// retiring are kept instead.
+    pub fn grow(&self, n: usize) -> usize {
+        let kept = self
+            .retiring
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |retiring| {
+                Some(retiring - retiring.min(n))
+            })
+            .expect("always some")
+            .min(n);
+        self.total.fetch_add(n, Ordering::AcqRel);
+        n - kept
+    }
+
+    /// Removes up to `n` blocks, marking them as retiring. Returns how many were removed.
+    pub fn shrink(&self, n: usize) -> usize {
+        let removed = self
+            .total
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |total| {
+                Some(total - total.min(n))
+            })
+            .expect("always some")
+            .min(n);
+        self.retiring.fetch_add(removed, Ordering::AcqRel);
+        removed
+    }
+
+    /// Claims a retiring block, returns whether the released block should be freed.
+    pub fn retire(&self) -> bool {
+        self.retiring
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |retiring| {
+                retiring.checked_sub(1)
+            })
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Capacity;
+
+    #[test]
+    fn grow_shrink() {
+        let capacity = Capacity::new(4);
+        assert_eq!(capacity.shrink(3), 3);
+        assert_eq!(capacity.total(), 1);
+        assert_eq!(capacity.retiring(), 3);
+
+        assert!(capacity.retire());
+        assert_eq!(capacity.retiring(), 2);
+
+        // retiring blocks are kept before new ones are allocated.
+        assert_eq!(capacity.grow(3), 1);
+        assert_eq!(capacity.total(), 4);
+        assert_eq!(capacity.retiring(), 0);
+        assert!(!capacity.retire());
+
+        assert_eq!(capacity.shrink(10), 4);
+        assert_eq!(capacity.total(), 0);
+    }
+}
@@ -0,0 +1,531 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug, Formatter},
+    hash::{Hash, Hasher},
+    io::{self, Read},
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::Error;
+
+use super::{BufferOwner, Owned, OwnedImpl, Pool, Shared, SharedImpl};
+
+/// An `Owned` buffer made of several pool blocks, so values larger than the pool's block size can
+/// be decoded.
+///
+/// Blocks are acquired from the pool on demand when [`Owned::reserve`] is called, which
+/// `full_decode` and `DecodeOwned` do before writing a value. The lengths they reserve come off
+/// the wire, so reserving never waits longer than [`ChainedOwned::with_timeout`] allows and fails
+/// rather than take more than the whole pool.
+pub struct ChainedOwned<P, R>
+where
+    P: Pool<Buffer = OwnedImpl> + Clone,
+    R: BufferOwner,
+{
+    pool: P,
+    reason: R,
+    // filled front to back, every block before the current one is full.
+    blocks: VecDeque<OwnedImpl>,
+    timeout: Option<Duration>,
+}
+
+impl<P, R> ChainedOwned<P, R>
+where
+    P: Pool<Buffer = OwnedImpl> + Clone,
+    R: BufferOwner,
+{
+    /// Creates an empty chain, blocks are acquired for `reason` as they are needed.
+    pub fn new(pool: P, reason: R) -> Self {
+        Self {
+            pool,
+            reason,
+            blocks: VecDeque::new(),
+            timeout: None,
+        }
+    }
+
+    /// Waits up to `timeout` in total for blocks to be released when reserving, instead of
+    /// failing as soon as the pool is exhausted.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the number of blocks held by the chain.
+    pub fn blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn current(&mut self) -> Option<&mut OwnedImpl> {
+        self.blocks
+            .iter_mut()
+            .find(|block| block.unfilled_capacity() > 0)
+    }
+}
+
+impl<P, R> Debug for ChainedOwned<P, R>
+where
+    P: Pool<Buffer = OwnedImpl> + Clone,
+    R: BufferOwner,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainedOwned")
+            .field("reason", &self.reason.why())
+            .field("blocks", &self.blocks)
+            .finish()
+    }
+}
+
+impl<P, R> Owned for ChainedOwned<P, R>
+where
+    P: Pool<Buffer = OwnedImpl> + Clone,
+    R: BufferOwner,
+{
+    type Shared = SharedRope;
+
+    /// Returns the unfilled part of the current block only.
+    fn unfilled(&mut self) -> &mut [u8] {
+        match self.current() {
+            Some(block) => block.unfilled(),
+            None => &mut [],
+        }
+    }
+
+    fn unfilled_capacity(&self) -> usize {
+        self.blocks.iter().map(Owned::unfilled_capacity).sum()
+    }
+
+    /// Acquires blocks from the pool until `additional` bytes fit.
+    ///
+    /// # Errors
+    /// Returns [`Error::OwnedRemaining`] if `additional` is more than the whole pool holds, or
+    /// [`Error::PoolExhausted`] if the pool runs out of blocks within the timeout. The blocks
+    /// acquired before the error go back to the pool.
+    fn reserve(&mut self, additional: usize) -> Result<(), Error> {
+        let most = self.pool.capacity().saturating_mul(self.pool.block_size());
+        if additional > most {
+            return Err(Error::OwnedRemaining {
+                acquire: additional,
+                capacity: most,
+            });
+        }
+
+        let start = Instant::now();
+        let held = self.blocks.len();
+        while self.unfilled_capacity() < additional {
+            let block = match self.timeout {
+                Some(timeout) => self
+                    .pool
+                    .acquire_timeout(self.reason, timeout.saturating_sub(start.elapsed())),
+                None => self
+                    .pool
+                    .try_acquire(self.reason)
+                    .ok_or(Error::PoolExhausted {
+                        owner: self.reason.why(),
+                    }),
+            };
+            match block {
+                Ok(block) => self.blocks.push_back(block),
+                Err(err) => {
+                    self.blocks.truncate(held);
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fill(&mut self, mut len: usize) {
+        while len > 0 {
+            let block = self.current().expect("fill past capacity");
+            let filled = len.min(block.unfilled_capacity());
+            block.fill(filled);
+            len -= filled;
+        }
+    }
+
+    /// Returns the filled part of the chain.
+    ///
+    /// # Panics
+    /// If more than one block has been filled, use [`Owned::filled_chunks`] instead.
+    fn filled(&self) -> &[u8] {
+        assert!(
+            self.blocks.iter().skip(1).all(|block| block.is_empty()),
+            "filled spans several blocks, use filled_chunks"
+        );
+        self.blocks.front().map(Owned::filled).unwrap_or_default()
+    }
+
+    fn filled_chunks(&self) -> impl Iterator<Item = &[u8]> {
+        self.blocks
+            .iter()
+            .filter(|block| !block.is_empty())
+            .map(Owned::filled)
+    }
+
+    fn filled_len(&self) -> usize {
+        self.blocks.iter().map(Owned::filled_len).sum()
+    }
+
+    fn into_shared(self) -> Self::Shared {
+        SharedRope::new(
+            self.blocks
+                .into_iter()
+                .map(Owned::into_shared)
+                .filter(|chunk| !chunk.is_empty()),
+        )
+    }
+
+    fn split_at(&mut self, index: usize) -> Self {
+        let mut left = VecDeque::new();
+        let mut remaining = index;
+        while remaining > 0 {
+            let block = self.blocks.front_mut().expect("split past capacity");
+            let capacity = block.filled_len() + block.unfilled_capacity();
+            if capacity <= remaining {
+                remaining -= capacity;
+                left.extend(self.blocks.pop_front());
+            } else {
+                left.push_back(block.split_at(remaining));
+                remaining = 0;
+            }
+        }
+
+        // empty blocks would keep their allocation from going back to the pool.
+        while self
+            .blocks
+            .front()
+            .is_some_and(|block| block.filled_len() + block.unfilled_capacity() == 0)
+        {
+            self.blocks.pop_front();
+        }
+
+        Self {
+            pool: self.pool.clone(),
+            reason: self.reason,
+            blocks: left,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// A read-only buffer made of several pool blocks.
+///
+/// Prefer [`SharedRope::chunks`] or [`SharedRope::reader`] to get at the bytes, `AsRef<[u8]>`
+/// copies the chunks into one contiguous buffer the first time it is called.
+#[derive(Clone, Default)]
+pub struct SharedRope {
+    chunks: Arc<[SharedImpl]>,
+    len: usize,
+    contiguous: Arc<OnceLock<Box<[u8]>>>,
+}
+
+impl SharedRope {
+    pub fn new(chunks: impl IntoIterator<Item = SharedImpl>) -> Self {
+        let chunks = chunks.into_iter().collect::<Arc<[_]>>();
+        let len = chunks.iter().map(Shared::len).sum();
+        Self {
+            chunks,
+            len,
+            contiguous: Default::default(),
+        }
+    }
+
+    /// Returns the chunks of the rope in order.
+    pub fn chunks(&self) -> impl Iterator<Item = &[u8]> {
+        self.chunks.iter().map(AsRef::as_ref)
+    }
+
+    /// Returns a reader over the bytes of the rope.
+    pub fn reader(&self) -> RopeReader<'_> {
+        RopeReader {
+            chunks: &self.chunks,
+            chunk: 0,
+            offset: 0,
+        }
+    }
+
+    fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.chunks().flatten().copied()
+    }
+}
+
+impl AsRef<[u8]> for SharedRope {
+    fn as_ref(&self) -> &[u8] {
+        match &*self.chunks {
+            [] => &[],
+            [chunk] => chunk.as_ref(),
+            _ => self
+                .contiguous
+                .get_or_init(|| self.chunks().flatten().copied().collect()),
+        }
+    }
+}
+
+impl Shared for SharedRope {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn chunks(&self) -> impl Iterator<Item = &[u8]> {
+        SharedRope::chunks(self)
+    }
+}
+
+impl Debug for SharedRope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedRope")
+            .field("chunks", &self.chunks().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl PartialEq for SharedRope {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.bytes().eq(other.bytes())
+    }
+}
+
+impl Eq for SharedRope {}
+
+#[cfg_attr(coverage_nightly, coverage(off))]
+impl PartialOrd for SharedRope {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg_attr(coverage_nightly, coverage(off))]
+impl Ord for SharedRope {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.bytes().cmp(other.bytes())
+    }
+}
+
+#[cfg_attr(coverage_nightly, coverage(off))]
+impl Hash for SharedRope {
+    // hashes the same regardless of how the bytes are chunked.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        for byte in self.bytes() {
+            state.write_u8(byte);
+        }
+    }
+}
+
+/// Reads the bytes of a [`SharedRope`] across its chunks.
+#[derive(Debug)]
+pub struct RopeReader<'a> {
+    chunks: &'a [SharedImpl],
+    chunk: usize,
+    offset: usize,
+}
+
+impl Read for RopeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(chunk) = self.chunks.get(self.chunk) {
+            let remaining = &chunk.as_ref()[self.offset..];
+            if remaining.is_empty() {
+                self.chunk += 1;
+                self.offset = 0;
+                continue;
+            }
+
+            let len = remaining.len().min(buf.len());
+            buf[..len].copy_from_slice(&remaining[..len]);
+            self.offset += len;
+            return Ok(len);
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Read},
+        time::Duration,
+    };
+
+    use matches::assert_matches;
+
+    use crate::{
+        buffer::{binary_data, byte_str, BinaryData, ByteStr, Owned, Pool, PoolImpl, Shared},
+        deque_codec::Enqueue,
+        full_decode, DecodeOwned, Encode, Error, Header, Kind, Packet,
+    };
+
+    use super::{ChainedOwned, SharedRope};
+
+    #[test]
+    fn decode_larger_than_block() {
+        let value = (0..=255).cycle().take(1000).collect::<Vec<u8>>();
+        let packet = Packet::Enqueue(Enqueue::new(
+            1,
+            1,
+            byte_str(b"kittens"),
+            binary_data(&value),
+        ));
+        let mut bytes = vec![];
+        packet.encode(&mut bytes).expect("encode");
+
+        let pool = PoolImpl::new(64, 32);
+        let mut buffer = ChainedOwned::new(pool.clone(), "chained");
+        let decoded = full_decode(&mut Cursor::new(bytes), &mut buffer, None).expect("decode");
+        let Packet::Enqueue(decoded) = decoded else {
+            panic!("expected enqueue, got {decoded:?}");
+        };
+
+        assert_eq!(decoded.path().as_str().expect("str"), "kittens");
+        let data = decoded.value().data();
+        assert_eq!(data.len(), 1000);
+        assert!(data.chunks().count() > 1);
+        assert!(data.chunks().all(|chunk| chunk.len() <= 64));
+
+        let mut read = vec![];
+        data.reader().read_to_end(&mut read).expect("read");
+        assert_eq!(read, value);
+        assert_eq!(data.as_ref(), value.as_slice());
+
+        let mut encoded = vec![];
+        decoded.value().encode(&mut encoded).expect("encode");
+        let mut expected = vec![];
+        binary_data(&value).encode(&mut expected).expect("encode");
+        assert_eq!(encoded, expected);
+
+        drop(decoded);
+        drop(buffer);
+        assert_eq!(pool.in_use(), 0);
+    }
+
+    #[test]
+    fn decode_str_across_blocks() {
+        let mut bytes = vec![];
+        byte_str("héllo wörld".as_bytes())
+            .encode(&mut bytes)
+            .expect("encode");
+
+        // `é` and `ö` are split across blocks.
+        let pool = PoolImpl::new(2, 8);
+        let mut buffer = ChainedOwned::new(pool, "chained");
+        let decoded = ByteStr::decode_owned(&mut bytes.as_slice(), &mut buffer).expect("decode");
+        assert!(decoded.data().chunks().count() > 1);
+        // validating didn't copy the rope into one buffer.
+        assert!(decoded.data().contiguous.get().is_none());
+        assert_eq!(decoded.as_str().expect("str"), "héllo wörld");
+    }
+
+    #[test]
+    fn filled_chunks() {
+        let pool = PoolImpl::new(4, 2);
+        let mut buffer = ChainedOwned::new(pool, "chained");
+        buffer.reserve(6).expect("reserve");
+        crate::fill(&mut buffer, &[1, 2]);
+        assert_eq!(buffer.filled(), &[1, 2]);
+
+        crate::fill(&mut buffer, &[3, 4, 5, 6]);
+        assert_eq!(
+            buffer.filled_chunks().collect::<Vec<_>>(),
+            [&[1, 2, 3, 4][..], &[5, 6]]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "filled spans several blocks")]
+    fn filled_across_blocks() {
+        let pool = PoolImpl::new(4, 2);
+        let mut buffer = ChainedOwned::new(pool, "chained");
+        buffer.reserve(6).expect("reserve");
+        crate::fill(&mut buffer, &[1, 2, 3, 4, 5, 6]);
+        let _ = buffer.filled();
+    }
+
+    #[test]
+    fn split_across_blocks() {
+        let pool = PoolImpl::new(4, 4);
+        let mut buffer = ChainedOwned::new(pool.clone(), "chained");
+        buffer.reserve(10).expect("reserve");
+        assert_eq!(buffer.blocks(), 3);
+        assert_eq!(buffer.unfilled_capacity(), 12);
+
+        let data = BinaryData::from_owned([1, 2, 3, 4, 5, 6], &mut buffer).expect("from_owned");
+        assert_eq!(
+            data.data().chunks().collect::<Vec<_>>(),
+            [&[1, 2, 3, 4][..], &[5, 6]]
+        );
+        assert_eq!(buffer.unfilled_capacity(), 6);
+        assert_eq!(buffer.blocks(), 2);
+        // only the first block has gone back to the pool.
+        drop(data);
+        assert_eq!(pool.in_use(), 2);
+
+        drop(buffer);
+        assert_eq!(pool.in_use(), 0);
+    }
+
+    #[test]
+    fn reserve_past_pool() {
+        // a header claiming more than the whole pool fails without taking any blocks.
+        let mut bytes = vec![];
+        Header::new(Kind::Enqueue, 1, 1, usize::MAX)
+            .encode(&mut bytes)
+            .expect("encode");
+        let pool = PoolImpl::new(64, 4);
+        let mut buffer = ChainedOwned::new(pool.clone(), "chained");
+        assert_matches!(
+            full_decode(&mut Cursor::new(bytes), &mut buffer, None),
+            Err(Error::BufferTooSmallForPacketDecode { capacity: 256, .. })
+        );
+        assert_eq!(buffer.blocks(), 0);
+        assert_eq!(pool.in_use(), 0);
+    }
+
+    #[test]
+    fn reserve_exhausted() {
+        let pool = PoolImpl::new(64, 4);
+        let held = pool.acquire("held");
+
+        // fits in the pool, but not in what is left of it.
+        let mut buffer = ChainedOwned::new(pool.clone(), "chained");
+        assert_matches!(
+            buffer.reserve(256),
+            Err(Error::PoolExhausted { owner: "chained" })
+        );
+        assert_eq!(buffer.blocks(), 0);
+        assert_eq!(pool.in_use(), 1);
+
+        let mut buffer = buffer.with_timeout(Duration::from_millis(1));
+        assert_matches!(
+            buffer.reserve(256),
+            Err(Error::PoolExhausted { owner: "chained" })
+        );
+        assert_eq!(pool.in_use(), 1);
+
+        drop(held);
+        buffer.reserve(256).expect("reserve");
+        assert_eq!(buffer.blocks(), 4);
+    }
+
+    #[test]
+    fn eq_ignores_chunking() {
+        let pool = PoolImpl::new(2, 4);
+        let mut buffer = ChainedOwned::new(pool.clone(), "chained");
+        let chained = BinaryData::from_owned([1, 2, 3], &mut buffer).expect("from_owned");
+
+        let pool = PoolImpl::new(4, 1);
+        let mut buffer = ChainedOwned::new(pool, "chained");
+        let single = BinaryData::from_owned([1, 2, 3], &mut buffer).expect("from_owned");
+
+        assert_eq!(chained, single);
+        assert_eq!(chained.data().chunks().count(), 2);
+        assert_eq!(single.data().chunks().count(), 1);
+        assert!(SharedRope::default().is_empty());
+    }
+}
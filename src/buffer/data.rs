@@ -7,7 +7,7 @@ use log::trace;
 
 use crate::{Decode, DecodeOwned, Encode, Error};
 
-use super::{Owned, Shared};
+use super::{fill, fill_from, Owned, Shared};
 
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
 pub struct BinaryData<S>
@@ -30,6 +30,7 @@ where
         owned: &mut impl Owned<Shared = S>,
     ) -> Result<Self, Error> {
         let len = data.as_ref().len();
+        owned.reserve(len)?;
         if owned.unfilled_capacity() < len {
             trace!("data: {:?}", data.as_ref());
             return Err(Error::OwnedRemaining {
@@ -37,9 +38,7 @@ where
                 capacity: owned.unfilled_capacity(),
             });
         }
-        let buffer = owned.unfilled();
-        buffer[..len].copy_from_slice(data.as_ref());
-        owned.fill(len);
+        fill(owned, data.as_ref());
         let data = owned.split_at(len);
         let data = data.into_shared();
 
@@ -59,6 +58,15 @@ where
     }
 }
 
+impl<S> From<Vec<u8>> for BinaryData<S>
+where
+    S: Shared + From<Vec<u8>>,
+{
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(S::from(data))
+    }
+}
+
 impl<S> Debug for BinaryData<S>
 where
     S: Shared,
@@ -80,19 +88,9 @@ where
         Self: Sized,
     {
         let len = usize::decode(reader)?;
-        if buffer.unfilled_capacity() < len {
-            return Err(Error::OwnedRemaining {
-                acquire: len,
-                capacity: buffer.unfilled_capacity(),
-            });
-        }
+        buffer.reserve(len)?;
+        fill_from(buffer, reader, len)?;
 
-        {
-            let buffer = buffer.unfilled();
-            reader.read_exact(&mut buffer[..len]).map_err(Error::Io)?;
-        }
-
-        buffer.fill(len);
         let data = buffer.split_at(len);
         let data = data.into_shared();
 
@@ -107,7 +105,9 @@ where
 {
     fn encode(&self, writer: &mut W) -> Result<(), Error> {
         self.len().encode(writer)?;
-        writer.write_all(self.data.as_ref()).map_err(Error::Io)?;
+        for chunk in self.data.chunks() {
+            writer.write_all(chunk).map_err(Error::Io)?;
+        }
 
         Ok(())
     }
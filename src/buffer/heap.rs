@@ -0,0 +1,232 @@
+use std::{cmp, sync::Arc};
+
+use crate::Error;
+
+use super::{Owned, Shared};
+
+impl Shared for Vec<u8> {}
+
+impl Shared for Arc<[u8]> {}
+
+impl Shared for &'static [u8] {}
+
+/// The most a `VecOwned` grows to unless another limit is set, see [`VecOwned::with_limit`].
+pub const DEFAULT_VEC_OWNED_LIMIT: usize = 64 * 1024 * 1024;
+
+/// A heap-backed `Owned` buffer that grows on demand.
+///
+/// Useful for clients and tools that do not want to manage a `PoolImpl`. Lengths read off the wire
+/// decide how much it grows, so it stops at a limit rather than let one bad packet exhaust memory.
+#[derive(Debug)]
+pub struct VecOwned {
+    data: Vec<u8>,
+    filled: usize,
+    limit: usize,
+}
+
+impl VecOwned {
+    pub fn new() -> Self {
+        Self::with_limit(DEFAULT_VEC_OWNED_LIMIT)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            data: vec![0; capacity],
+            filled: 0,
+            limit: DEFAULT_VEC_OWNED_LIMIT.max(capacity),
+        }
+    }
+
+    /// Creates a buffer that never grows past `limit` bytes, filled and unfilled.
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            data: Vec::new(),
+            filled: 0,
+            limit,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl Default for VecOwned {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Owned for VecOwned {
+    type Shared = Arc<[u8]>;
+
+    fn unfilled(&mut self) -> &mut [u8] {
+        &mut self.data[self.filled..]
+    }
+
+    fn unfilled_capacity(&self) -> usize {
+        self.data.len() - self.filled
+    }
+
+    fn reserve(&mut self, additional: usize) -> Result<(), Error> {
+        let unfilled = self.unfilled_capacity();
+        if unfilled >= additional {
+            return Ok(());
+        }
+        if additional > self.limit - self.filled {
+            return Err(Error::OwnedRemaining {
+                acquire: additional,
+                capacity: self.limit - self.filled,
+            });
+        }
+        self.data.resize(self.filled + additional, 0);
+
+        Ok(())
+    }
+
+    fn fill(&mut self, len: usize) {
+        self.filled += len;
+    }
+
+    fn filled(&self) -> &[u8] {
+        &self.data[..self.filled]
+    }
+
+    fn filled_len(&self) -> usize {
+        self.filled
+    }
+
+    /// Only the filled part of the buffer is kept.
+    fn into_shared(mut self) -> Self::Shared {
+        self.data.truncate(self.filled);
+        self.data.into()
+    }
+
+    fn split_at(&mut self, index: usize) -> Self {
+        let right = self.data.split_off(index);
+        let left = std::mem::replace(&mut self.data, right);
+        let other = Self {
+            data: left,
+            filled: cmp::min(self.filled, index),
+            limit: self.limit,
+        };
+
+        self.filled -= other.filled;
+
+        other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, sync::Arc};
+
+    use matches::assert_matches;
+
+    use crate::{
+        buffer::{BinaryData, ByteStr, Owned},
+        full_decode,
+        kv_store_codec::Put,
+        DecodeOwned, Encode, Error, Header, Kind, Packet,
+    };
+
+    use super::VecOwned;
+
+    #[test]
+    fn grows_on_demand() {
+        let mut owned = VecOwned::new();
+        assert_eq!(owned.unfilled_capacity(), 0);
+
+        let data = BinaryData::from_owned([1, 2, 3], &mut owned).expect("from_owned");
+        assert_eq!(data.data().as_ref(), &[1, 2, 3]);
+        assert_eq!(owned.unfilled_capacity(), 0);
+        assert!(owned.is_empty());
+    }
+
+    #[test]
+    fn split_at() {
+        let mut owned = VecOwned::with_capacity(8);
+        owned.unfilled()[..6].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+        owned.fill(6);
+
+        let left = owned.split_at(4);
+        assert_eq!(left.filled(), &[1, 2, 3, 4]);
+        assert_eq!(left.unfilled_capacity(), 0);
+        assert_eq!(owned.filled(), &[5, 6]);
+        assert_eq!(owned.unfilled_capacity(), 2);
+        assert_eq!(left.into_shared().as_ref(), &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn conversions() {
+        let data = BinaryData::<Vec<u8>>::from(vec![1, 2, 3]);
+        assert_eq!(data.data().as_slice(), &[1, 2, 3]);
+
+        let data = BinaryData::<Arc<[u8]>>::from(vec![1, 2, 3]);
+        assert_eq!(data.data().as_ref(), &[1, 2, 3]);
+
+        let str = ByteStr::<Vec<u8>>::from("kittens".to_owned());
+        assert_eq!(str.as_str().expect("str"), "kittens");
+    }
+
+    #[test]
+    fn full_decode_without_pool() {
+        let put = Packet::Put(Put::new(
+            1,
+            1,
+            BinaryData::<&'static [u8]>::new(b"kittens"),
+            BinaryData::new(&[42u8; 2048][..]),
+        ));
+        let mut bytes = vec![];
+        put.encode(&mut bytes).expect("encode");
+
+        let mut owned = VecOwned::new();
+        let decoded = full_decode(&mut Cursor::new(bytes), &mut owned, None).expect("decode");
+        let Packet::Put(decoded) = decoded else {
+            panic!("expected put, got {decoded:?}");
+        };
+        assert_eq!(decoded.key().data().as_ref(), b"kittens");
+        assert_eq!(decoded.value().data().as_ref(), &[42u8; 2048]);
+    }
+
+    #[test]
+    fn limit() {
+        let mut owned = VecOwned::with_limit(8);
+        owned.reserve(8).expect("reserve");
+        assert_eq!(owned.unfilled_capacity(), 8);
+        owned.fill(4);
+        assert_matches!(
+            owned.reserve(5),
+            Err(Error::OwnedRemaining {
+                acquire: 5,
+                capacity: 4
+            })
+        );
+        assert_eq!(owned.unfilled_capacity(), 4);
+    }
+
+    #[test]
+    fn decode_past_limit() {
+        // a header claiming more than the limit is rejected before anything is allocated.
+        let mut bytes = vec![];
+        Header::new(Kind::Put, 1, 1, usize::MAX)
+            .encode(&mut bytes)
+            .expect("encode");
+        let mut owned = VecOwned::with_limit(1024);
+        assert_matches!(
+            full_decode(&mut Cursor::new(bytes), &mut owned, None),
+            Err(Error::BufferTooSmallForPacketDecode { capacity: 1024, .. })
+        );
+        assert_eq!(owned.unfilled_capacity(), 0);
+
+        // so is a length prefix, even with the default limit.
+        let mut bytes = vec![];
+        (usize::MAX / 2).encode(&mut bytes).expect("encode");
+        let mut owned = VecOwned::new();
+        assert_matches!(
+            BinaryData::decode_owned(&mut Cursor::new(bytes), &mut owned),
+            Err(Error::OwnedRemaining { .. })
+        );
+        assert_eq!(owned.unfilled_capacity(), 0);
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::warn;
+
+/// A buffer that was acquired from a pool and has not been released yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutstandingBuffer {
+    /// The `BufferOwner::why` given when the buffer was acquired.
+    pub owner: &'static str,
+    /// When the buffer was acquired.
+    pub acquired_at: Instant,
+}
+
+impl OutstandingBuffer {
+    /// Returns how long the buffer has been held.
+    pub fn held(&self) -> Duration {
+        self.acquired_at.elapsed()
+    }
+}
+
+/// Records every buffer a pool hands out until it is released.
+#[derive(Debug, Default)]
+pub(crate) struct LeakTracker {
+    next_id: AtomicU64,
+    outstanding: Mutex<HashMap<u64, OutstandingBuffer>>,
+    reported: AtomicUsize,
+}
+
+impl LeakTracker {
+    pub fn track(&self, owner: &'static str) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.outstanding.lock().expect("outstanding lock").insert(
+            id,
+            OutstandingBuffer {
+                owner,
+                acquired_at: Instant::now(),
+            },
+        );
+        id
+    }
+
+    pub fn untrack(&self, id: u64) {
+        self.outstanding
+            .lock()
+            .expect("outstanding lock")
+            .remove(&id);
+    }
+
+    /// Returns the outstanding buffers, oldest first. Buffers acquired at the same instant are in
+    /// the order they were acquired.
+    pub fn outstanding(&self) -> Vec<OutstandingBuffer> {
+        let mut outstanding = self
+            .outstanding
+            .lock()
+            .expect("outstanding lock")
+            .iter()
+            .map(|(id, buffer)| (*id, *buffer))
+            .collect::<Vec<_>>();
+        outstanding.sort_by_key(|(id, buffer)| (buffer.acquired_at, *id));
+        outstanding.into_iter().map(|(_, buffer)| buffer).collect()
+    }
+
+    /// Logs every outstanding buffer as leaked.
+    pub fn report(&self) {
+        for buffer in self.outstanding() {
+            warn!(
+                "buffer for {} was never returned to the pool (held for {:?})",
+                buffer.owner,
+                buffer.held()
+            );
+            self.reported.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of buffers that have been reported as leaked.
+    #[cfg(test)]
+    pub fn reported(&self) -> usize {
+        self.reported.load(Ordering::Relaxed)
+    }
+}
+
+/// Reports buffers that were never returned once the last handle to the pool is dropped.
+#[derive(Debug)]
+pub(crate) struct LeakGuard(pub Arc<LeakTracker>);
+
+impl Drop for LeakGuard {
+    fn drop(&mut self) {
+        self.0.report();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LeakTracker;
+
+    #[test]
+    fn track_untrack() {
+        let tracker = LeakTracker::default();
+        let first = tracker.track("first");
+        let second = tracker.track("second");
+
+        let outstanding = tracker.outstanding();
+        assert_eq!(outstanding.len(), 2);
+        assert_eq!(outstanding[0].owner, "first");
+        assert_eq!(outstanding[1].owner, "second");
+
+        tracker.untrack(first);
+        let outstanding = tracker.outstanding();
+        assert_eq!(outstanding.len(), 1);
+        assert_eq!(outstanding[0].owner, "second");
+
+        tracker.untrack(second);
+        assert!(tracker.outstanding().is_empty());
+    }
+
+    #[test]
+    fn report() {
+        let tracker = LeakTracker::default();
+        let first = tracker.track("first");
+        tracker.track("second");
+        tracker.untrack(first);
+
+        tracker.report();
+        assert_eq!(tracker.reported(), 1);
+    }
+}
@@ -1,10 +1,24 @@
-use std::{fmt::Debug, hash::Hash, sync::Arc};
+use std::{fmt::Debug, hash::Hash, io::Read, sync::Arc, time::Duration};
 
 mod block;
 
+mod cache;
+
+mod capacity;
+
+mod chain;
+pub use chain::{ChainedOwned, RopeReader, SharedRope};
+
 mod data;
-use crossbeam_channel::Sender;
+use crossbeam_channel::{Receiver, Sender};
 pub use data::BinaryData;
+use log::trace;
+
+mod heap;
+pub use heap::{VecOwned, DEFAULT_VEC_OWNED_LIMIT};
+
+mod leak;
+pub use leak::OutstandingBuffer;
 
 mod owned;
 pub use owned::OwnedImpl;
@@ -12,13 +26,36 @@ pub use owned::OwnedImpl;
 mod pool;
 pub use pool::PoolImpl;
 
+mod reserve;
+pub use reserve::ReservedFor;
+
 mod shared;
 pub use shared::SharedImpl;
 
+mod stats;
+pub use stats::PoolStats;
+
 mod str;
 pub use str::ByteStr;
 
-use self::block::Block;
+mod tiered;
+pub use tiered::TieredPool;
+
+#[cfg(feature = "async")]
+mod waiters;
+#[cfg(feature = "async")]
+pub use waiters::Acquire;
+
+mod zero;
+pub use zero::ZeroPolicy;
+
+use crate::{Error, Kind};
+
+#[cfg(feature = "async")]
+use self::waiters::AsyncWaiters;
+use self::{
+    block::Memory, cache::ThreadCache, capacity::Capacity, leak::LeakTracker, stats::Metrics,
+};
 
 /// A thread-safe read-only buffer.
 pub trait Shared:
@@ -38,6 +75,11 @@ pub trait Shared:
     fn as_slice(&self) -> &[u8] {
         self.as_ref()
     }
+
+    /// Returns the buffer as a sequence of contiguous chunks.
+    fn chunks(&self) -> impl Iterator<Item = &[u8]> {
+        std::iter::once(self.as_ref())
+    }
 }
 
 /// A read-write buffer.
@@ -49,18 +91,32 @@ pub trait Owned {
         self.filled_len() == 0
     }
 
-    /// Returns a mutable slice of the buffer.
+    /// Returns a mutable slice of the unfilled part of the buffer.
+    /// For buffers made of several blocks this is only the current block, so it can be shorter
+    /// than `unfilled_capacity`.
     fn unfilled(&mut self) -> &mut [u8];
 
     /// Returns the capacity of the unfilled part of the buffer.
     fn unfilled_capacity(&self) -> usize;
 
+    /// Ensures the unfilled part of the buffer can hold at least `additional` bytes.
+    /// Buffers that cannot grow ignore this and keep their capacity, buffers that can return an
+    /// error rather than grow past their limit.
+    fn reserve(&mut self, _additional: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
     /// Fills the buffer with the given length.
     fn fill(&mut self, len: usize);
 
-    /// Returns a slice of the buffer.
+    /// Returns a slice of the filled part of the buffer.
     fn filled(&self) -> &[u8];
 
+    /// Returns the filled part of the buffer as a sequence of contiguous chunks.
+    fn filled_chunks(&self) -> impl Iterator<Item = &[u8]> {
+        std::iter::once(self.filled())
+    }
+
     /// Returns the length of the filled part of the buffer.
     fn filled_len(&self) -> usize;
 
@@ -71,46 +127,188 @@ pub trait Owned {
     fn split_at(&mut self, index: usize) -> Self;
 }
 
+/// Copies as much of `data` as fits into the unfilled part of the buffer.
 pub fn fill<O>(buffer: &mut O, data: &[u8])
 where
     O: Owned,
 {
-    let len = data.len().min(buffer.unfilled_capacity());
-    buffer.unfilled()[..len].copy_from_slice(&data[..len]);
-    buffer.fill(len);
+    let mut data = &data[..data.len().min(buffer.unfilled_capacity())];
+    while !data.is_empty() {
+        let unfilled = buffer.unfilled();
+        let len = data.len().min(unfilled.len());
+        unfilled[..len].copy_from_slice(&data[..len]);
+        buffer.fill(len);
+        data = &data[len..];
+    }
+}
+
+/// Reads exactly `len` bytes from the reader into the unfilled part of the buffer.
+///
+/// If the read fails part way the bytes it wrote are zeroed, as they are never filled and so
+/// would be missed by [`ZeroPolicy::Used`].
+pub(crate) fn fill_from<R, O>(buffer: &mut O, reader: &mut R, mut len: usize) -> Result<(), Error>
+where
+    R: Read,
+    O: Owned,
+{
+    if buffer.unfilled_capacity() < len {
+        return Err(Error::OwnedRemaining {
+            acquire: len,
+            capacity: buffer.unfilled_capacity(),
+        });
+    }
+
+    while len > 0 {
+        let unfilled = buffer.unfilled();
+        let read = len.min(unfilled.len());
+        if let Err(err) = reader.read_exact(&mut unfilled[..read]) {
+            unfilled[..read].fill(0);
+            return Err(Error::Io(err));
+        }
+        buffer.fill(read);
+        len -= read;
+    }
+
+    Ok(())
 }
 
 pub trait BufferOwner: Copy {
     fn why(&self) -> &'static str;
+
+    /// The kind of packet the buffer is acquired for, if known. Used to match reservations made
+    /// with [`ReservedFor::Codec`].
+    fn kind(&self) -> Option<Kind> {
+        None
+    }
 }
 
-// TODO: add a utilization fn for the pool
 pub trait Pool {
     type Buffer: Owned;
 
     fn acquire(&self, reason: impl BufferOwner) -> Self::Buffer;
 
+    /// Acquires a buffer only if one is available right now.
+    fn try_acquire(&self, reason: impl BufferOwner) -> Option<Self::Buffer>;
+
+    /// Acquires a buffer, waiting at most `timeout` for one to be released.
+    ///
+    /// # Errors
+    /// Returns [`Error::PoolExhausted`] if no buffer was released in time.
+    fn acquire_timeout(
+        &self,
+        reason: impl BufferOwner,
+        timeout: Duration,
+    ) -> Result<Self::Buffer, Error>;
+
     fn block_size(&self) -> usize;
 
     fn capacity(&self) -> usize;
+
+    /// Returns the number of blocks that can be acquired without waiting.
+    fn available(&self) -> usize {
+        self.capacity().saturating_sub(self.in_use())
+    }
+
+    /// Returns the number of blocks currently acquired.
+    fn in_use(&self) -> usize;
+
+    /// Returns a snapshot of the pool's utilization.
+    fn stats(&self) -> PoolStats;
+}
+
+/// Where released blocks go back to, shared by a pool and every block it hands out.
+#[derive(Clone, Debug)]
+pub(crate) struct ReturnPath {
+    sender: Sender<Memory>,
+    cache: Option<Arc<ThreadCache>>,
+    capacity: Arc<Capacity>,
+    #[cfg(feature = "async")]
+    waiters: Arc<AsyncWaiters>,
+}
+
+impl ReturnPath {
+    /// The waiters only hold the receiver weakly, so once the pool is dropped the channel
+    /// disconnects and released blocks are freed instead of sent back.
+    #[cfg_attr(not(feature = "async"), allow(unused_variables))]
+    pub fn new(sender: Sender<Memory>, receiver: &Arc<Receiver<Memory>>, capacity: usize) -> Self {
+        Self {
+            sender,
+            cache: None,
+            capacity: Arc::new(Capacity::new(capacity)),
+            #[cfg(feature = "async")]
+            waiters: Arc::new(AsyncWaiters::new(Arc::downgrade(receiver))),
+        }
+    }
+
+    /// Returns a block to the pool, handing it to the first async waiter if there is one.
+    /// Returns `false` if the pool is gone.
+    /// Gives a block released by `owner` back, unless the pool has shrunk and it is freed instead.
+    fn release(&self, memory: Memory, owner: &'static str) {
+        if self.capacity.retire() {
+            trace!("pool has shrunk, freeing buffer for {}", owner);
+            return;
+        }
+        if !self.give_back(memory) {
+            trace!("pool is gone, freeing buffer for {}", owner);
+        }
+    }
+
+    fn give_back(&self, memory: Memory) -> bool {
+        #[cfg(feature = "async")]
+        let waiting = self.waiters.waiting();
+        #[cfg(not(feature = "async"))]
+        let waiting = false;
+
+        let memory = match &self.cache {
+            Some(cache) if !waiting => match cache.push(memory, &self.sender) {
+                Ok(()) => return true,
+                Err(memory) => memory,
+            },
+            _ => memory,
+        };
+        if self.sender.send(memory).is_err() {
+            return false;
+        }
+        #[cfg(feature = "async")]
+        self.waiters.dispatch(self);
+        true
+    }
 }
 
 /// A mechanism for releasing memory back to the pool.
-/// When this is dropped, it releases the memory back to the pool.
-#[derive(Clone)]
-pub(crate) struct Releaser(Arc<Sender<Block>>);
+/// It is owned by the block's allocation and runs once the last view of the block is dropped.
+pub(crate) struct Releaser {
+    returns: ReturnPath,
+    metrics: Arc<Metrics>,
+    owner: &'static str,
+    leak: Option<(Arc<LeakTracker>, u64)>,
+    zero: ZeroPolicy,
+}
 
 impl Releaser {
-    pub fn new(sender: Sender<Block>) -> Self {
-        Self(Arc::new(sender))
+    pub fn new(
+        returns: ReturnPath,
+        metrics: Arc<Metrics>,
+        owner: &'static str,
+        leak: Option<(Arc<LeakTracker>, u64)>,
+        zero: ZeroPolicy,
+    ) -> Self {
+        Self {
+            returns,
+            metrics,
+            owner,
+            leak,
+            zero,
+        }
     }
 
-    fn release(&mut self, buffer: &mut Block) {
-        if Arc::strong_count(&self.0) == 1 {
-            self.0
-                .send(buffer.release())
-                .expect("failed to release buffer");
+    fn release(self, mut memory: Memory, used: usize) {
+        self.zero.zero(&mut memory, used);
+        self.metrics.released(self.owner);
+        if let Some((tracker, id)) = &self.leak {
+            tracker.untrack(*id);
         }
+        self.returns.release(memory, self.owner);
     }
 }
 
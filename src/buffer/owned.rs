@@ -1,30 +1,31 @@
-use std::cmp;
+use std::{
+    cmp,
+    fmt::{self, Debug, Formatter},
+};
 
 use buffer::Owned;
 
-use crate::buffer::{self, block::Block, Releaser};
+use crate::buffer::{self, block::Block};
 
 use super::shared::SharedImpl;
 
 pub struct OwnedImpl {
     inner: Block,
     filled: usize,
-    releaser: Releaser,
 }
 
 impl OwnedImpl {
-    pub(crate) fn new(inner: Block, releaser: Releaser) -> Self {
-        Self {
-            inner,
-            filled: 0,
-            releaser,
-        }
+    pub(crate) fn new(inner: Block) -> Self {
+        Self { inner, filled: 0 }
     }
 }
 
-impl Drop for OwnedImpl {
-    fn drop(&mut self) {
-        self.releaser.release(&mut self.inner);
+impl Debug for OwnedImpl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedImpl")
+            .field("inner", &self.inner)
+            .field("filled", &self.filled)
+            .finish()
     }
 }
 
@@ -42,6 +43,7 @@ impl Owned for OwnedImpl {
 
     fn fill(&mut self, len: usize) {
         self.filled += len;
+        self.inner.mark_used(self.filled);
     }
 
     fn filled(&self) -> &[u8] {
@@ -54,14 +56,13 @@ impl Owned for OwnedImpl {
     }
 
     fn into_shared(self) -> Self::Shared {
-        SharedImpl::new(self.inner.clone(), self.releaser.clone())
+        SharedImpl::new(self.inner.into_shared())
     }
 
     fn split_at(&mut self, index: usize) -> Self {
         let other = Self {
             inner: self.inner.split_at(index),
             filled: cmp::min(self.filled, index),
-            releaser: self.releaser.clone(),
         };
 
         self.filled -= other.filled;
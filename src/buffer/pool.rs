@@ -1,31 +1,249 @@
-use crossbeam_channel::{bounded, Receiver, Sender};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{unbounded, Receiver, Select};
 use log::trace;
 
-use super::{block::Block, BufferOwner, OwnedImpl, Pool, Releaser};
+use crate::Error;
+
+use super::{
+    block::{Block, Memory},
+    cache::ThreadCache,
+    leak::{LeakGuard, LeakTracker},
+    reserve::Reserve,
+    stats::Metrics,
+    BufferOwner, OutstandingBuffer, OwnedImpl, Pool, PoolStats, Releaser, ReservedFor, ReturnPath,
+    ZeroPolicy,
+};
 
 #[derive(Clone)]
 pub struct PoolImpl {
-    tx: Sender<Block>,
-    rx: Receiver<Block>,
+    pub(super) returns: ReturnPath,
+    // shared so async waiters can hold it weakly, see `ReturnPath::new`.
+    pub(super) rx: Arc<Receiver<Memory>>,
+    metrics: Arc<Metrics>,
+    leaks: Option<Arc<LeakGuard>>,
+    zero: ZeroPolicy,
+    reserve: Option<Reserve>,
 
     block_size: usize,
-    capacity: usize,
 }
 
 impl PoolImpl {
     pub fn new(block_size: usize, capacity: usize) -> Self {
-        let (tx, rx) = bounded(capacity);
+        let (tx, rx) = unbounded();
+        let rx = Arc::new(rx);
 
+        let pool = Self {
+            returns: ReturnPath::new(tx, &rx, capacity),
+            rx,
+            metrics: Arc::new(Metrics::default()),
+            leaks: None,
+            zero: ZeroPolicy::default(),
+            reserve: None,
+            block_size,
+        };
         for _ in 0..capacity {
-            tx.send(Block::new(block_size)).expect("fill pool");
+            pool.add_block();
+        }
+        pool
+    }
+
+    /// Adds `n` blocks to the pool. Blocks that are still waiting to be retired by an earlier
+    /// [`PoolImpl::shrink`] are kept rather than allocating new ones.
+    pub fn grow(&self, n: usize) {
+        for _ in 0..self.returns.capacity.grow(n) {
+            self.add_block();
         }
+    }
 
-        Self {
-            tx,
-            rx,
-            block_size,
-            capacity,
+    /// Removes up to `n` blocks from the pool and returns how many were removed.
+    ///
+    /// Blocks that are not acquired are freed right away, the rest are freed as they are released
+    /// instead of going back to the pool. [`Pool::capacity`] reflects the change immediately, so
+    /// [`Pool::in_use`] can exceed it until enough blocks have been released.
+    pub fn shrink(&self, n: usize) -> usize {
+        let capacity = &self.returns.capacity;
+        let removed = capacity.shrink(n);
+        if let Some(cache) = &self.returns.cache {
+            cache.flush(&self.returns.sender);
         }
+        while capacity.retiring() > 0 {
+            let Ok(memory) = self.rx.try_recv() else {
+                break;
+            };
+            if !capacity.retire() {
+                // a concurrent grow kept the block.
+                self.returns.give_back(memory);
+                break;
+            }
+        }
+        removed
+    }
+
+    fn add_block(&self) {
+        self.returns
+            .give_back(vec![0; self.block_size].into_boxed_slice());
+    }
+
+    /// Counts this pool's acquires and releases in `parent` too.
+    pub(super) fn with_parent_metrics(mut self, parent: Arc<Metrics>) -> Self {
+        self.metrics = Arc::new(Metrics::with_parent(parent));
+        self
+    }
+
+    /// Sets how blocks are cleared when they are released, see [`ZeroPolicy`].
+    /// Defaults to [`ZeroPolicy::Always`].
+    pub fn with_zero_policy(mut self, zero: ZeroPolicy) -> Self {
+        self.zero = zero;
+        self
+    }
+
+    /// Keeps up to `depth` released blocks per thread in front of the pool's channel, so threads
+    /// that acquire and release in quick succession do not contend on it. Blocks cached by other
+    /// threads are flushed back to the channel before an acquire would wait, so every block can
+    /// still be acquired. A depth of 0 disables the cache.
+    pub fn with_thread_cache(mut self, depth: usize) -> Self {
+        self.returns.cache = (depth > 0).then(|| Arc::new(ThreadCache::new(depth)));
+        self
+    }
+
+    /// Sets aside `blocks` extra blocks that only the owners matching one of `classes` can acquire,
+    /// so that, for example, `system_codec` packets can still be decoded when data traffic has
+    /// acquired every other block. Replaces any earlier reservation.
+    ///
+    /// Matching owners take from the rest of the pool first and fall back to the reserved blocks,
+    /// which always return to the reservation when released. [`Pool::capacity`] includes them.
+    pub fn with_reserved(
+        mut self,
+        blocks: usize,
+        classes: impl IntoIterator<Item = ReservedFor>,
+    ) -> Self {
+        self.reserve = Some(Reserve::new(self.block_size, blocks, classes));
+        self
+    }
+
+    /// Returns the reservation `owner` may use, if any.
+    pub(super) fn reserve_for(&self, owner: &impl BufferOwner) -> Option<&Reserve> {
+        self.reserve
+            .as_ref()
+            .filter(|reserve| reserve.matches(owner))
+    }
+
+    /// Returns where a waiting `owner` can be handed blocks from, the reservation too if it may
+    /// use it.
+    #[cfg(feature = "async")]
+    pub(super) fn wait_paths(&self, owner: &impl BufferOwner) -> impl Iterator<Item = &ReturnPath> {
+        std::iter::once(&self.returns)
+            .chain(self.reserve_for(owner).map(|reserve| &reserve.returns))
+    }
+
+    /// Records the owner and acquire time of every buffer handed out, so that buffers which are
+    /// held for too long or never returned can be found. Any buffers still outstanding when the
+    /// last clone of the pool is dropped are logged.
+    ///
+    /// This adds a lock to every acquire and release, so it is meant for debugging.
+    pub fn with_leak_detection(mut self) -> Self {
+        self.leaks = Some(Arc::new(LeakGuard(Arc::new(LeakTracker::default()))));
+        self
+    }
+
+    /// Returns the buffers that have not been released yet, oldest first.
+    /// Always empty unless leak detection is enabled.
+    pub fn outstanding(&self) -> Vec<OutstandingBuffer> {
+        self.leaks
+            .as_ref()
+            .map(|leaks| leaks.0.outstanding())
+            .unwrap_or_default()
+    }
+
+    /// Returns the buffers that have been held for at least `threshold`, oldest first.
+    /// Always empty unless leak detection is enabled.
+    pub fn long_held(&self, threshold: Duration) -> Vec<OutstandingBuffer> {
+        self.outstanding()
+            .into_iter()
+            .filter(|buffer| buffer.held() >= threshold)
+            .collect()
+    }
+
+    /// Returns the number of released blocks sitting in thread caches.
+    /// Always 0 unless the thread cache is enabled.
+    pub fn cached(&self) -> usize {
+        self.returns
+            .cache
+            .as_ref()
+            .map_or(0, |cache| cache.cached())
+    }
+
+    /// Waits for a block, from the reservation too if `owner` may use it. Returns `None` if the
+    /// timeout passes first.
+    fn recv(
+        &self,
+        owner: &impl BufferOwner,
+        timeout: Option<Duration>,
+    ) -> Option<(Memory, &ReturnPath)> {
+        let Some(reserve) = self.reserve_for(owner) else {
+            let block = match timeout {
+                Some(timeout) => self.wait(|| self.rx.recv_timeout(timeout)).ok(),
+                None => self.wait(|| self.rx.recv()).ok(),
+            };
+            return block.map(|block| (block, &self.returns));
+        };
+
+        let start = Instant::now();
+        let mut select = Select::new();
+        select.recv(&self.rx);
+        select.recv(&reserve.rx);
+        self.wait(|| loop {
+            let index = match timeout {
+                Some(timeout) => select
+                    .ready_timeout(timeout.saturating_sub(start.elapsed()))
+                    .ok()?,
+                None => select.ready(),
+            };
+
+            // another thread may have won the race for this block.
+            let block = match index {
+                0 => self.rx.try_recv().map(|block| (block, &self.returns)),
+                _ => reserve.rx.try_recv().map(|block| (block, &reserve.returns)),
+            };
+            if let Ok(block) = block {
+                return Some(block);
+            }
+        })
+    }
+
+    /// Runs a blocking receive, making sure no released block is left sitting in a thread cache.
+    fn wait<T>(&self, recv: impl FnOnce() -> T) -> T {
+        match &self.returns.cache {
+            Some(cache) => cache.waiting(&self.returns.sender, recv),
+            None => recv(),
+        }
+    }
+
+    pub(super) fn wrap(
+        &self,
+        memory: Memory,
+        returns: &ReturnPath,
+        owner: &'static str,
+        waited: Duration,
+    ) -> OwnedImpl {
+        trace!("acquired buffer for {}", owner);
+        self.metrics.acquired(owner, waited);
+        let leak = self
+            .leaks
+            .as_ref()
+            .map(|leaks| (leaks.0.clone(), leaks.0.track(owner)));
+        let releaser = Releaser::new(
+            returns.clone(),
+            self.metrics.clone(),
+            owner,
+            leak,
+            self.zero,
+        );
+        OwnedImpl::new(Block::new(memory, Some(releaser)))
     }
 }
 
@@ -33,16 +251,61 @@ impl Pool for PoolImpl {
     type Buffer = OwnedImpl;
 
     fn acquire(&self, reason: impl BufferOwner) -> Self::Buffer {
-        trace!("acquiring buffer for {}", reason.why());
         #[cfg(feature = "timeout")]
-        let block = self
-            .rx
-            .recv_timeout(std::time::Duration::from_secs(1))
-            .expect(&format!("failed to acquire buffer for {}", reason.why()));
+        {
+            self.acquire_timeout(reason, Duration::from_secs(1))
+                .unwrap_or_else(|err| panic!("{}", err))
+        }
         #[cfg(not(feature = "timeout"))]
-        let block = self.rx.recv().expect("failed to acquire buffer");
-        trace!("acquired buffer for {}", reason.why());
-        OwnedImpl::new(block, Releaser::new(self.tx.clone()))
+        {
+            trace!("acquiring buffer for {}", reason.why());
+            if let Some(buffer) = self.try_acquire(reason) {
+                return buffer;
+            }
+
+            let start = Instant::now();
+            let (block, returns) = self.recv(&reason, None).expect("failed to acquire buffer");
+            self.wrap(block, returns, reason.why(), start.elapsed())
+        }
+    }
+
+    fn try_acquire(&self, reason: impl BufferOwner) -> Option<Self::Buffer> {
+        let block = match &self.returns.cache {
+            Some(cache) => cache.pop().or_else(|| {
+                self.rx.try_recv().ok().or_else(|| {
+                    cache.flush(&self.returns.sender);
+                    self.rx.try_recv().ok()
+                })
+            }),
+            None => self.rx.try_recv().ok(),
+        };
+        let (block, returns) = match block {
+            Some(block) => (block, &self.returns),
+            None => {
+                let reserve = self.reserve_for(&reason)?;
+                (reserve.rx.try_recv().ok()?, &reserve.returns)
+            }
+        };
+        Some(self.wrap(block, returns, reason.why(), Duration::ZERO))
+    }
+
+    fn acquire_timeout(
+        &self,
+        reason: impl BufferOwner,
+        timeout: Duration,
+    ) -> Result<Self::Buffer, Error> {
+        trace!("acquiring buffer for {} within {:?}", reason.why(), timeout);
+        if let Some(buffer) = self.try_acquire(reason) {
+            return Ok(buffer);
+        }
+
+        let start = Instant::now();
+        let (block, returns) = self
+            .recv(&reason, Some(timeout))
+            .ok_or(Error::PoolExhausted {
+                owner: reason.why(),
+            })?;
+        Ok(self.wrap(block, returns, reason.why(), start.elapsed()))
     }
 
     fn block_size(&self) -> usize {
@@ -50,14 +313,24 @@ impl Pool for PoolImpl {
     }
 
     fn capacity(&self) -> usize {
-        self.capacity
+        self.returns.capacity.total() + self.reserve.as_ref().map_or(0, Reserve::blocks)
+    }
+
+    fn in_use(&self) -> usize {
+        self.metrics.in_use()
+    }
+
+    fn stats(&self) -> PoolStats {
+        self.metrics.snapshot(self.capacity())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use matches::assert_matches;
+
     use super::*;
-    use crate::buffer::Owned;
+    use crate::{buffer::Owned, Codec, Kind};
 
     #[test]
     fn acquire() {
@@ -108,6 +381,320 @@ mod tests {
         assert_eq!(buffer1.unfilled_capacity(), 1024);
     }
 
+    #[test]
+    fn stats() {
+        let pool = PoolImpl::new(1024, 2);
+        assert_eq!(pool.available(), 2);
+        assert_eq!(pool.in_use(), 0);
+
+        let mut buffer1 = pool.acquire("decode");
+        let buffer2 = pool.acquire("encode");
+        assert_eq!(pool.available(), 0);
+        assert_eq!(pool.in_use(), 2);
+
+        let stats = pool.stats();
+        assert_eq!(stats.high_water_mark, 2);
+        assert_eq!(stats.total_acquires, 2);
+        assert_eq!(stats.waits, 0);
+        assert_eq!(stats.owners.get("decode"), Some(&1));
+        assert_eq!(stats.owners.get("encode"), Some(&1));
+
+        // splitting does not acquire another block.
+        let left = buffer1.split_at(512).into_shared();
+        drop(buffer1);
+        assert_eq!(pool.in_use(), 2);
+        drop(left);
+        drop(buffer2);
+
+        let stats = pool.stats();
+        assert_eq!(stats.in_use, 0);
+        assert_eq!(stats.available, 2);
+        assert_eq!(stats.high_water_mark, 2);
+        assert!(stats.owners.is_empty());
+    }
+
+    #[test_case::test_case(ZeroPolicy::Always, [0; 8]; "always")]
+    #[test_case::test_case(ZeroPolicy::Used, [0, 0, 0, 9, 9, 9, 9, 9]; "used")]
+    #[test_case::test_case(ZeroPolicy::Never, [1, 2, 3, 9, 9, 9, 9, 9]; "never")]
+    #[test_case::test_case(ZeroPolicy::Secure, [0; 8]; "secure")]
+    fn zero_policy(zero: ZeroPolicy, expected: [u8; 8]) {
+        let pool = PoolImpl::new(8, 1).with_zero_policy(zero);
+        let mut buffer = pool.acquire("test");
+        // written but never filled.
+        buffer.unfilled()[3..].fill(9);
+        crate::fill(&mut buffer, &[1, 2, 3]);
+        drop(buffer);
+
+        let mut buffer = pool.acquire("test");
+        assert_eq!(buffer.unfilled(), &expected);
+    }
+
+    #[test]
+    fn zero_used_after_failed_read() {
+        let pool = PoolImpl::new(8, 1).with_zero_policy(ZeroPolicy::Used);
+        let mut buffer = pool.acquire("test");
+        assert_matches!(
+            // reads the 3 bytes there are before failing.
+            crate::buffer::fill_from(
+                &mut buffer,
+                &mut std::io::Read::take(std::io::repeat(1), 3),
+                8
+            ),
+            Err(Error::Io(_))
+        );
+        assert_eq!(buffer.filled_len(), 0);
+        drop(buffer);
+
+        let mut buffer = pool.acquire("test");
+        assert_eq!(buffer.unfilled(), &[0; 8]);
+    }
+
+    #[test]
+    fn leak_detection() {
+        let pool = PoolImpl::new(1024, 3);
+        let _untracked = pool.acquire("untracked");
+        assert!(pool.outstanding().is_empty());
+
+        let pool = PoolImpl::new(1024, 3).with_leak_detection();
+        let mut decode = pool.acquire("decode");
+        let cached = decode.split_at(16).into_shared();
+        drop(decode);
+        let encode = pool.acquire("encode");
+
+        let outstanding = pool.outstanding();
+        assert_eq!(outstanding.len(), 2);
+        assert_eq!(outstanding[0].owner, "decode");
+        assert_eq!(outstanding[1].owner, "encode");
+
+        let long_held = pool.long_held(Duration::ZERO);
+        assert_eq!(long_held.len(), 2);
+        assert_eq!(long_held[0].owner, "decode");
+        assert!(pool.long_held(Duration::from_secs(3600)).is_empty());
+
+        drop(cached);
+        drop(encode);
+        assert!(pool.outstanding().is_empty());
+    }
+
+    #[test]
+    fn released_after_pool_dropped() {
+        let pool = PoolImpl::new(8, 2);
+        let buffer = pool.acquire("held");
+        let returns = pool.returns.clone();
+        drop(pool);
+
+        // the channel has disconnected, so blocks are freed rather than sent back.
+        assert!(!returns.give_back(vec![0; 8].into_boxed_slice()));
+        drop(buffer);
+    }
+
+    #[test]
+    fn leak_reported_on_drop() {
+        let pool = PoolImpl::new(1024, 2).with_leak_detection();
+        let tracker = pool.leaks.as_ref().expect("leak detection").0.clone();
+        let leaked = pool.acquire("leaked");
+        drop(pool.acquire("returned"));
+
+        // every clone of the pool has to go before the guard reports.
+        let clone = pool.clone();
+        drop(pool);
+        assert_eq!(tracker.reported(), 0);
+        drop(clone);
+        assert_eq!(tracker.reported(), 1);
+
+        // releasing after the pool is gone is fine.
+        drop(leaked);
+        assert!(tracker.outstanding().is_empty());
+    }
+
+    #[test]
+    fn try_acquire() {
+        let pool = PoolImpl::new(1024, 1);
+        let buffer = pool.try_acquire("test").expect("buffer");
+        assert!(pool.try_acquire("test").is_none());
+        drop(buffer);
+        assert!(pool.try_acquire("test").is_some());
+    }
+
+    #[test]
+    fn acquire_timeout() {
+        let pool = PoolImpl::new(1024, 1);
+        let buffer = pool
+            .acquire_timeout("test", Duration::from_millis(1))
+            .expect("buffer");
+        assert_eq!(buffer.unfilled_capacity(), 1024);
+        assert_matches!(
+            pool.acquire_timeout("exhausted", Duration::from_millis(1)),
+            Err(Error::PoolExhausted { owner: "exhausted" })
+        );
+        assert_eq!(pool.in_use(), 1);
+        assert_eq!(pool.stats().owners.get("exhausted"), None);
+    }
+
+    #[test]
+    fn grow_shrink() {
+        let pool = PoolImpl::new(8, 2);
+        let buffers = (0..2).map(|_| pool.acquire("test")).collect::<Vec<_>>();
+        assert!(pool.try_acquire("test").is_none());
+
+        pool.grow(2);
+        assert_eq!(pool.capacity(), 4);
+        let grown = (0..2).map(|_| pool.acquire("grown")).collect::<Vec<_>>();
+        assert_eq!(pool.in_use(), 4);
+        drop(grown);
+
+        // the two idle blocks are freed now, the other two once they are released.
+        assert_eq!(pool.shrink(3), 3);
+        assert_eq!(pool.capacity(), 1);
+        assert_eq!(pool.available(), 0);
+        assert!(pool.try_acquire("test").is_none());
+
+        drop(buffers);
+        assert_eq!(pool.in_use(), 0);
+        assert_eq!(pool.stats().available, 1);
+        let buffer = pool.try_acquire("test").expect("buffer");
+        assert!(pool.try_acquire("test").is_none());
+        drop(buffer);
+
+        assert_eq!(pool.shrink(5), 1);
+        assert_eq!(pool.capacity(), 0);
+        pool.grow(1);
+        assert!(pool.try_acquire("test").is_some());
+    }
+
+    #[test]
+    fn grow_keeps_retiring_blocks() {
+        let pool = PoolImpl::new(8, 1).with_thread_cache(4);
+        let buffer = pool.acquire("test");
+        assert_eq!(pool.shrink(1), 1);
+        pool.grow(1);
+        assert_eq!(pool.capacity(), 1);
+
+        drop(buffer);
+        let _buffer = pool.try_acquire("test").expect("buffer");
+        assert!(pool.try_acquire("test").is_none());
+    }
+
+    #[derive(Clone, Copy)]
+    struct Decoding(Kind);
+
+    impl BufferOwner for Decoding {
+        fn why(&self) -> &'static str {
+            "decoding"
+        }
+
+        fn kind(&self) -> Option<Kind> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn reserved() {
+        let pool = PoolImpl::new(8, 2).with_reserved(
+            1,
+            [
+                ReservedFor::Codec(Codec::System),
+                ReservedFor::Owner("operator"),
+            ],
+        );
+        assert_eq!(pool.capacity(), 3);
+
+        let data = (0..2)
+            .map(|_| pool.acquire(Decoding(Kind::Enqueue)))
+            .collect::<Vec<_>>();
+        assert!(pool.try_acquire(Decoding(Kind::Put)).is_none());
+        assert_matches!(
+            pool.acquire_timeout("test", Duration::from_millis(1)),
+            Err(Error::PoolExhausted { owner: "test" })
+        );
+
+        let ping = pool.try_acquire(Decoding(Kind::Ping)).expect("reserved");
+        assert!(pool.try_acquire("operator").is_none());
+        assert_eq!(pool.in_use(), 3);
+
+        // reserved blocks go back to the reservation.
+        drop(ping);
+        assert!(pool.try_acquire(Decoding(Kind::Get)).is_none());
+        let operator = pool
+            .acquire_timeout("operator", Duration::from_millis(1))
+            .expect("reserved");
+
+        // matching owners use the rest of the pool first.
+        drop(data);
+        drop(operator);
+        let report = pool.acquire(Decoding(Kind::Report));
+        let data = pool.try_acquire("test").expect("unreserved");
+        let join = pool.acquire(Decoding(Kind::Join));
+        assert!(pool.try_acquire("operator").is_none());
+        drop((report, join, data));
+        assert_eq!(pool.in_use(), 0);
+    }
+
+    #[test]
+    fn reserved_waiter() {
+        let pool = PoolImpl::new(8, 1).with_reserved(1, [ReservedFor::Owner("operator")]);
+        let data = pool.acquire("test");
+        let reserved = pool.acquire("operator");
+
+        let waiter = {
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                pool.acquire_timeout("operator", Duration::from_secs(10))
+                    .map(|buffer| buffer.unfilled_capacity())
+            })
+        };
+        std::thread::sleep(Duration::from_millis(10));
+        drop(reserved);
+        assert_eq!(waiter.join().expect("join").expect("buffer"), 8);
+        drop(data);
+    }
+
+    #[test]
+    fn thread_cache() {
+        let pool = PoolImpl::new(8, 4).with_thread_cache(2);
+        let buffers = (0..4).map(|_| pool.acquire("test")).collect::<Vec<_>>();
+        assert_eq!(pool.in_use(), 4);
+
+        // released on another thread, which keeps some of them cached.
+        std::thread::spawn(move || drop(buffers))
+            .join()
+            .expect("join");
+        assert_eq!(pool.in_use(), 0);
+        assert!(pool.cached() > 0);
+
+        // every block can still be acquired from this thread.
+        let buffers = (0..4)
+            .map(|_| pool.try_acquire("test").expect("buffer"))
+            .collect::<Vec<_>>();
+        assert_eq!(pool.cached(), 0);
+        assert!(pool.try_acquire("test").is_none());
+        assert_eq!(pool.stats().total_acquires, 8);
+
+        drop(buffers);
+        assert_eq!(pool.cached(), 2);
+        let buffer = pool.try_acquire("test").expect("buffer");
+        assert_eq!(pool.cached(), 1);
+        drop(buffer);
+    }
+
+    #[test]
+    fn thread_cache_wakes_waiter() {
+        let pool = PoolImpl::new(8, 1).with_thread_cache(4);
+        let buffer = pool.acquire("test");
+
+        let waiter = {
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                pool.acquire_timeout("waiter", Duration::from_secs(10))
+                    .map(|buffer| buffer.unfilled_capacity())
+            })
+        };
+        // whether the release lands before or after the waiter starts waiting, it must get it.
+        std::thread::sleep(Duration::from_millis(10));
+        drop(buffer);
+        assert_eq!(waiter.join().expect("join").expect("buffer"), 8);
+    }
+
     #[cfg(feature = "timeout")]
     #[test]
     #[should_panic]
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use crossbeam_channel::{unbounded, Receiver};
+
+use crate::Codec;
+
+use super::{block::Memory, BufferOwner, ReturnPath};
+
+/// Who may use the blocks reserved with [`PoolImpl::with_reserved`](super::PoolImpl::with_reserved).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReservedFor {
+    /// Buffers acquired for this [`BufferOwner::why`].
+    Owner(&'static str),
+    /// Buffers acquired for packets of this codec, see [`BufferOwner::kind`].
+    Codec(Codec),
+}
+
+impl ReservedFor {
+    fn matches(self, owner: &impl BufferOwner) -> bool {
+        match self {
+            ReservedFor::Owner(why) => owner.why() == why,
+            ReservedFor::Codec(codec) => owner.kind().is_some_and(|kind| kind.codec() == codec),
+        }
+    }
+}
+
+/// Blocks set aside from the rest of a pool. They are only handed to the owners the reservation
+/// is for, and always return to the reservation when released.
+#[derive(Clone, Debug)]
+pub(crate) struct Reserve {
+    pub returns: ReturnPath,
+    pub rx: Arc<Receiver<Memory>>,
+    classes: Arc<[ReservedFor]>,
+}
+
+impl Reserve {
+    pub fn new(
+        block_size: usize,
+        blocks: usize,
+        classes: impl IntoIterator<Item = ReservedFor>,
+    ) -> Self {
+        let (tx, rx) = unbounded();
+        let rx = Arc::new(rx);
+        let returns = ReturnPath::new(tx, &rx, blocks);
+        for _ in 0..blocks {
+            returns.give_back(vec![0; block_size].into_boxed_slice());
+        }
+        Self {
+            returns,
+            rx,
+            classes: classes.into_iter().collect(),
+        }
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.returns.capacity.total()
+    }
+
+    pub fn matches(&self, owner: &impl BufferOwner) -> bool {
+        self.classes.iter().any(|class| class.matches(owner))
+    }
+}
@@ -4,20 +4,11 @@ use std::{
     hash::Hash,
 };
 
-use super::{Block, Releaser};
+use super::block::{Block, SharedBlock};
 
 #[derive(Clone)]
 pub struct SharedImpl {
-    inner: Block,
-    _releaser: Option<Releaser>,
-}
-
-impl Drop for SharedImpl {
-    fn drop(&mut self) {
-        if let Some(mut releaser) = self._releaser.take() {
-            releaser.release(&mut self.inner);
-        }
-    }
+    inner: SharedBlock,
 }
 
 impl Debug for SharedImpl {
@@ -59,19 +50,14 @@ impl Hash for SharedImpl {
 }
 
 impl SharedImpl {
-    pub(crate) fn new(inner: Block, releaser: Releaser) -> Self {
-        Self {
-            inner,
-            _releaser: Some(releaser),
-        }
+    pub(crate) fn new(inner: SharedBlock) -> Self {
+        Self { inner }
     }
 
     pub fn test_new(data: &[u8]) -> Self {
-        let mut block = Block::new(data.len());
-        block.as_mut_slice().copy_from_slice(data);
+        let block = Block::new(data.into(), None);
         Self {
-            inner: block,
-            _releaser: None,
+            inner: block.into_shared(),
         }
     }
 }
@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// A point in time snapshot of how a pool is being used.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// The number of blocks the pool manages.
+    pub capacity: usize,
+    /// The number of blocks that can be acquired without waiting.
+    pub available: usize,
+    /// The number of blocks currently acquired.
+    pub in_use: usize,
+    /// The most blocks that have been acquired at the same time.
+    pub high_water_mark: usize,
+    /// The number of acquires over the lifetime of the pool.
+    pub total_acquires: u64,
+    /// The number of acquires that had to wait for a block to be released.
+    pub waits: u64,
+    /// The total time spent waiting for blocks to be released.
+    pub total_wait: Duration,
+    /// The longest time spent waiting for a block to be released.
+    pub max_wait: Duration,
+    /// The number of blocks in use keyed by `BufferOwner::why`.
+    pub owners: HashMap<&'static str, usize>,
+}
+
+impl PoolStats {
+    /// Returns the fraction of the pool currently in use, between `0.0` and `1.0`.
+    pub fn utilization(&self) -> f64 {
+        if self.capacity == 0 {
+            return 0.0;
+        }
+        self.in_use as f64 / self.capacity as f64
+    }
+
+    /// Returns the average time an acquire waited, only counting acquires that had to wait.
+    pub fn mean_wait(&self) -> Duration {
+        if self.waits == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = self.total_wait.as_nanos() / u128::from(self.waits);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+}
+
+/// Counters shared between a pool and the buffers it hands out.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    in_use: AtomicUsize,
+    high_water_mark: AtomicUsize,
+    total_acquires: AtomicU64,
+    waits: AtomicU64,
+    total_wait_nanos: AtomicU64,
+    max_wait_nanos: AtomicU64,
+    owners: Mutex<HashMap<&'static str, usize>>,
+    // counts the acquires and releases of several pools together, see `TieredPool`.
+    parent: Option<Arc<Metrics>>,
+}
+
+impl Metrics {
+    pub fn with_parent(parent: Arc<Metrics>) -> Self {
+        Self {
+            parent: Some(parent),
+            ..Self::default()
+        }
+    }
+
+    pub fn acquired(&self, owner: &'static str, waited: Duration) {
+        let in_use = self.in_use.fetch_add(1, Ordering::AcqRel) + 1;
+        self.high_water_mark.fetch_max(in_use, Ordering::AcqRel);
+        self.total_acquires.fetch_add(1, Ordering::Relaxed);
+
+        if !waited.is_zero() {
+            let nanos = u64::try_from(waited.as_nanos()).unwrap_or(u64::MAX);
+            self.waits.fetch_add(1, Ordering::Relaxed);
+            self.total_wait_nanos.fetch_add(nanos, Ordering::Relaxed);
+            self.max_wait_nanos.fetch_max(nanos, Ordering::Relaxed);
+        }
+
+        *self
+            .owners
+            .lock()
+            .expect("owners lock")
+            .entry(owner)
+            .or_default() += 1;
+
+        if let Some(parent) = &self.parent {
+            parent.acquired(owner, waited);
+        }
+    }
+
+    pub fn released(&self, owner: &'static str) {
+        self.in_use.fetch_sub(1, Ordering::AcqRel);
+
+        let mut owners = self.owners.lock().expect("owners lock");
+        if let Some(count) = owners.get_mut(owner) {
+            *count -= 1;
+            if *count == 0 {
+                owners.remove(owner);
+            }
+        }
+        drop(owners);
+
+        if let Some(parent) = &self.parent {
+            parent.released(owner);
+        }
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::Acquire)
+    }
+
+    pub fn snapshot(&self, capacity: usize) -> PoolStats {
+        let in_use = self.in_use();
+        PoolStats {
+            capacity,
+            available: capacity.saturating_sub(in_use),
+            in_use,
+            high_water_mark: self.high_water_mark.load(Ordering::Acquire),
+            total_acquires: self.total_acquires.load(Ordering::Relaxed),
+            waits: self.waits.load(Ordering::Relaxed),
+            total_wait: Duration::from_nanos(self.total_wait_nanos.load(Ordering::Relaxed)),
+            max_wait: Duration::from_nanos(self.max_wait_nanos.load(Ordering::Relaxed)),
+            owners: self.owners.lock().expect("owners lock").clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use super::{Metrics, PoolStats};
+
+    #[test]
+    fn snapshot() {
+        let metrics = Metrics::default();
+        metrics.acquired("decode", Duration::ZERO);
+        metrics.acquired("decode", Duration::from_millis(4));
+        metrics.acquired("encode", Duration::from_millis(2));
+        metrics.released("decode");
+
+        let stats = metrics.snapshot(4);
+        assert_eq!(stats.capacity, 4);
+        assert_eq!(stats.in_use, 2);
+        assert_eq!(stats.available, 2);
+        assert_eq!(stats.high_water_mark, 3);
+        assert_eq!(stats.total_acquires, 3);
+        assert_eq!(stats.waits, 2);
+        assert_eq!(stats.total_wait, Duration::from_millis(6));
+        assert_eq!(stats.max_wait, Duration::from_millis(4));
+        assert_eq!(stats.mean_wait(), Duration::from_millis(3));
+        assert_eq!(stats.utilization(), 0.5);
+        assert_eq!(stats.owners.get("decode"), Some(&1));
+        assert_eq!(stats.owners.get("encode"), Some(&1));
+
+        metrics.released("encode");
+        let stats = metrics.snapshot(4);
+        assert_eq!(stats.owners.get("encode"), None);
+    }
+
+    #[test]
+    fn parent() {
+        let parent = Arc::new(Metrics::default());
+        let a = Metrics::with_parent(parent.clone());
+        let b = Metrics::with_parent(parent.clone());
+
+        a.acquired("a", Duration::ZERO);
+        a.released("a");
+        b.acquired("b", Duration::from_millis(2));
+
+        let stats = parent.snapshot(2);
+        assert_eq!(stats.in_use, 1);
+        assert_eq!(stats.high_water_mark, 1);
+        assert_eq!(stats.total_acquires, 2);
+        assert_eq!(stats.waits, 1);
+        assert_eq!(stats.owners.get("a"), None);
+        assert_eq!(stats.owners.get("b"), Some(&1));
+    }
+
+    #[test]
+    fn mean_wait_past_u32() {
+        let stats = PoolStats {
+            waits: u64::from(u32::MAX) * 4,
+            total_wait: Duration::from_nanos(u64::from(u32::MAX) * 8),
+            ..PoolStats::default()
+        };
+        assert_eq!(stats.mean_wait(), Duration::from_nanos(2));
+    }
+}
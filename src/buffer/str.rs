@@ -51,6 +51,24 @@ where
     pub fn as_str(&self) -> Result<&str, std::str::Utf8Error> {
         std::str::from_utf8(self.0.data().as_slice())
     }
+
+    /// Returns the string without checking that it is valid UTF-8.
+    ///
+    /// # Safety
+    /// The bytes must be valid UTF-8, which is the case for strings made with `from_owned` or
+    /// decoded with `decode_owned`.
+    pub unsafe fn as_str_unchecked(&self) -> &str {
+        std::str::from_utf8_unchecked(self.0.data().as_slice())
+    }
+}
+
+impl<S> From<String> for ByteStr<S>
+where
+    S: Shared + From<Vec<u8>>,
+{
+    fn from(data: String) -> Self {
+        Self::new(BinaryData::from(data.into_bytes()))
+    }
 }
 
 impl<S> Debug for ByteStr<S>
@@ -58,7 +76,10 @@ where
     S: Shared,
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self.as_str().expect("str"))
+        match self.as_str() {
+            Ok(str) => write!(f, "{:?}", str),
+            Err(_) => write!(f, "b\"{}\"", self.as_slice().escape_ascii()),
+        }
     }
 }
 
@@ -72,6 +93,10 @@ where
         Self: Sized,
     {
         let data = BinaryData::decode_owned(reader, buffer)?;
+        if !is_utf8(data.data().chunks()) {
+            // only copies the chunks together to say where the bad bytes are.
+            std::str::from_utf8(data.data().as_slice()).map_err(Error::InvalidUtf8)?;
+        }
 
         Ok(Self(data))
     }
@@ -89,8 +114,45 @@ where
     }
 }
 
+/// Checks `chunks` are valid UTF-8 when joined, without joining them. A character split across
+/// chunks is carried over to the next one.
+fn is_utf8<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> bool {
+    let mut carry = [0; 4];
+    let mut carried = 0;
+    for mut chunk in chunks {
+        if carried > 0 {
+            let take = (carry.len() - carried).min(chunk.len());
+            carry[carried..carried + take].copy_from_slice(&chunk[..take]);
+            match std::str::from_utf8(&carry[..carried + take]) {
+                Ok(_) => chunk = &chunk[take..],
+                // the carried character is complete, carry on after it.
+                Err(err) if err.valid_up_to() > 0 => chunk = &chunk[err.valid_up_to() - carried..],
+                Err(err) if err.error_len().is_some() => return false,
+                Err(_) => {
+                    carried += take;
+                    continue;
+                }
+            }
+            carried = 0;
+        }
+
+        if let Err(err) = std::str::from_utf8(chunk) {
+            if err.error_len().is_some() {
+                return false;
+            }
+            let rest = &chunk[err.valid_up_to()..];
+            carry[..rest.len()].copy_from_slice(rest);
+            carried = rest.len();
+        }
+    }
+
+    carried == 0
+}
+
 #[cfg(test)]
 mod tests {
+    use matches::assert_matches;
+
     use crate::{Pool, PoolImpl};
 
     use super::*;
@@ -103,8 +165,49 @@ mod tests {
         let byte_str = ByteStr::from_owned(data, &mut buffer).expect("byte_str");
 
         assert_eq!(byte_str.len(), data.len());
-        assert_eq!(byte_str.is_empty(), false);
+        assert!(!byte_str.is_empty());
         assert_eq!(byte_str.as_str().expect("str"), data);
         assert_eq!(byte_str.data().as_slice(), b"hello world");
+        // SAFETY: made from a `&str`.
+        assert_eq!(unsafe { byte_str.as_str_unchecked() }, data);
+        assert_eq!(format!("{:?}", byte_str), "\"hello world\"");
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let pool = PoolImpl::new(1024, 1);
+        let mut buffer = pool.acquire("test");
+        let mut bytes = vec![];
+        crate::binary_data(b"bad \xff path")
+            .encode(&mut bytes)
+            .expect("encode");
+
+        let err = ByteStr::decode_owned(&mut bytes.as_slice(), &mut buffer).unwrap_err();
+        assert_matches!(err, Error::InvalidUtf8(_));
+
+        let byte_str = crate::byte_str(b"bad \xff path\n");
+        assert!(byte_str.as_str().is_err());
+        assert_eq!(format!("{:?}", byte_str), "b\"bad \\xff path\\n\"");
+    }
+
+    #[test]
+    fn utf8_across_chunks() {
+        let strings: [&[u8]; 6] = [
+            "héllo wörld 😀".as_bytes(),
+            b"",
+            b"bad \xff path",
+            &"😀".as_bytes()[..3],
+            b"\xc3(",
+            b"\xf0\x9f\x98x",
+        ];
+        for bytes in strings {
+            for size in 1..=bytes.len().max(1) {
+                assert_eq!(
+                    is_utf8(bytes.chunks(size)),
+                    std::str::from_utf8(bytes).is_ok(),
+                    "{bytes:?} in chunks of {size}"
+                );
+            }
+        }
     }
 }
@@ -0,0 +1,314 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::Select;
+use log::trace;
+
+use crate::Error;
+
+use super::{stats::Metrics, BufferOwner, OwnedImpl, Pool, PoolImpl, PoolStats};
+
+/// A pool made up of several size classes of blocks.
+///
+/// Buffers are taken from the smallest class that can hold the requested length, falling back to
+/// larger classes when the smaller ones are exhausted.
+#[derive(Clone)]
+pub struct TieredPool {
+    // sorted by block size, smallest first.
+    classes: Vec<PoolImpl>,
+    // every class counts its acquires and releases here too, so the stats of the whole pool, such
+    // as its high water mark, are those of the combined pool rather than sums over the classes.
+    metrics: Arc<Metrics>,
+}
+
+impl TieredPool {
+    /// Creates a pool from `(block_size, capacity)` pairs, one per size class.
+    ///
+    /// # Panics
+    /// If no size classes are given.
+    pub fn new(classes: &[(usize, usize)]) -> Self {
+        assert!(!classes.is_empty(), "tiered pool needs a size class");
+
+        let metrics = Arc::new(Metrics::default());
+        let mut classes = classes
+            .iter()
+            .map(|&(block_size, capacity)| {
+                PoolImpl::new(block_size, capacity).with_parent_metrics(metrics.clone())
+            })
+            .collect::<Vec<_>>();
+        classes.sort_by_key(|class| class.block_size());
+
+        Self { classes, metrics }
+    }
+
+    /// Returns the size classes, smallest first.
+    pub fn classes(&self) -> &[PoolImpl] {
+        &self.classes
+    }
+
+    /// Returns the stats of each size class keyed by block size, smallest first.
+    pub fn class_stats(&self) -> Vec<(usize, PoolStats)> {
+        self.classes
+            .iter()
+            .map(|class| (class.block_size(), class.stats()))
+            .collect()
+    }
+
+    /// Acquires a buffer of at least `len` bytes, waiting for one to be released if every
+    /// fitting class is exhausted.
+    ///
+    /// # Errors
+    /// Returns [`Error::OwnedRemaining`] if `len` is larger than the largest size class.
+    pub fn acquire_sized(&self, len: usize, reason: impl BufferOwner) -> Result<OwnedImpl, Error> {
+        self.acquire_sized_inner(len, reason, None)
+    }
+
+    /// Acquires a buffer of at least `len` bytes only if one is available right now.
+    ///
+    /// # Errors
+    /// Returns [`Error::OwnedRemaining`] if `len` is larger than the largest size class.
+    pub fn try_acquire_sized(
+        &self,
+        len: usize,
+        reason: impl BufferOwner,
+    ) -> Result<Option<OwnedImpl>, Error> {
+        let fitting = self.fitting(len)?;
+        Ok(fitting.iter().find_map(|class| class.try_acquire(reason)))
+    }
+
+    /// Acquires a buffer of at least `len` bytes, waiting at most `timeout` for one to be
+    /// released.
+    ///
+    /// # Errors
+    /// Returns [`Error::OwnedRemaining`] if `len` is larger than the largest size class, or
+    /// [`Error::PoolExhausted`] if no fitting buffer was released in time.
+    pub fn acquire_sized_timeout(
+        &self,
+        len: usize,
+        reason: impl BufferOwner,
+        timeout: Duration,
+    ) -> Result<OwnedImpl, Error> {
+        self.acquire_sized_inner(len, reason, Some(timeout))
+    }
+
+    fn largest(&self) -> &PoolImpl {
+        self.classes.last().expect("tiered pool needs a size class")
+    }
+
+    fn fitting(&self, len: usize) -> Result<&[PoolImpl], Error> {
+        let start = self
+            .classes
+            .iter()
+            .position(|class| class.block_size() >= len)
+            .ok_or(Error::OwnedRemaining {
+                acquire: len,
+                capacity: self.block_size(),
+            })?;
+        Ok(&self.classes[start..])
+    }
+
+    fn acquire_sized_inner(
+        &self,
+        len: usize,
+        reason: impl BufferOwner,
+        timeout: Option<Duration>,
+    ) -> Result<OwnedImpl, Error> {
+        trace!("acquiring buffer of {} bytes for {}", len, reason.why());
+        let fitting = self.fitting(len)?;
+        if let Some(buffer) = fitting.iter().find_map(|class| class.try_acquire(reason)) {
+            return Ok(buffer);
+        }
+
+        // wait on every class that fits and take whichever releases first.
+        let start = Instant::now();
+        let mut select = Select::new();
+        for class in fitting {
+            select.recv(&class.rx);
+        }
+
+        loop {
+            let index = match timeout {
+                Some(timeout) => select
+                    .ready_timeout(timeout.saturating_sub(start.elapsed()))
+                    .map_err(|_| Error::PoolExhausted {
+                        owner: reason.why(),
+                    })?,
+                None => select.ready(),
+            };
+
+            let class = &fitting[index];
+            // another thread may have won the race for this block.
+            if let Ok(block) = class.rx.try_recv() {
+                return Ok(class.wrap(block, &class.returns, reason.why(), start.elapsed()));
+            }
+        }
+    }
+}
+
+impl Pool for TieredPool {
+    type Buffer = OwnedImpl;
+
+    /// Acquires a buffer from the largest size class.
+    fn acquire(&self, reason: impl BufferOwner) -> Self::Buffer {
+        self.largest().acquire(reason)
+    }
+
+    fn try_acquire(&self, reason: impl BufferOwner) -> Option<Self::Buffer> {
+        self.largest().try_acquire(reason)
+    }
+
+    fn acquire_timeout(
+        &self,
+        reason: impl BufferOwner,
+        timeout: Duration,
+    ) -> Result<Self::Buffer, Error> {
+        self.largest().acquire_timeout(reason, timeout)
+    }
+
+    /// Returns the block size of the largest size class.
+    fn block_size(&self) -> usize {
+        self.largest().block_size()
+    }
+
+    /// Returns the number of blocks across all size classes.
+    fn capacity(&self) -> usize {
+        self.classes.iter().map(Pool::capacity).sum()
+    }
+
+    fn in_use(&self) -> usize {
+        self.classes.iter().map(Pool::in_use).sum()
+    }
+
+    /// Returns the stats of all size classes combined. The high water mark is the most blocks
+    /// acquired at the same time across every class, see [`TieredPool::class_stats`] for the
+    /// peak of each class.
+    fn stats(&self) -> PoolStats {
+        self.metrics.snapshot(self.capacity())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, time::Duration};
+
+    use matches::assert_matches;
+
+    use crate::{
+        buffer::{binary_data, Owned, Pool},
+        full_decode,
+        kv_store_codec::{test_key, Put},
+        Decode, Encode, Error, Header, Packet,
+    };
+
+    use super::TieredPool;
+
+    #[test]
+    fn picks_smallest_fitting_class() {
+        let pool = TieredPool::new(&[(1024, 1), (64, 1), (256, 1)]);
+        assert_eq!(pool.block_size(), 1024);
+        assert_eq!(pool.capacity(), 3);
+
+        let small = pool.acquire_sized(10, "small").expect("small");
+        assert_eq!(small.unfilled_capacity(), 64);
+
+        // falls back to the next class when the smallest is exhausted.
+        let next = pool.acquire_sized(10, "small").expect("next");
+        assert_eq!(next.unfilled_capacity(), 256);
+
+        let big = pool.acquire_sized(512, "big").expect("big");
+        assert_eq!(big.unfilled_capacity(), 1024);
+
+        assert_matches!(pool.try_acquire_sized(10, "small"), Ok(None));
+        assert_matches!(
+            pool.acquire_sized_timeout(10, "small", Duration::from_millis(1)),
+            Err(Error::PoolExhausted { owner: "small" })
+        );
+        assert_matches!(
+            pool.acquire_sized(2048, "huge"),
+            Err(Error::OwnedRemaining {
+                acquire: 2048,
+                capacity: 1024
+            })
+        );
+
+        drop(small);
+        let small = pool.try_acquire_sized(10, "small").expect("fits");
+        assert_eq!(small.expect("small").unfilled_capacity(), 64);
+    }
+
+    #[test]
+    fn stats() {
+        let pool = TieredPool::new(&[(64, 2), (256, 1)]);
+        let _small = pool.acquire_sized(10, "small").expect("small");
+        let _big = pool.acquire_sized(100, "big").expect("big");
+
+        let stats = pool.stats();
+        assert_eq!(stats.capacity, 3);
+        assert_eq!(stats.in_use, 2);
+        assert_eq!(stats.available, 1);
+        assert_eq!(stats.total_acquires, 2);
+        assert_eq!(stats.owners.get("small"), Some(&1));
+        assert_eq!(stats.owners.get("big"), Some(&1));
+
+        let classes = pool.class_stats();
+        assert_eq!(classes[0].0, 64);
+        assert_eq!(classes[0].1.in_use, 1);
+        assert_eq!(classes[1].0, 256);
+        assert_eq!(classes[1].1.in_use, 1);
+    }
+
+    #[test]
+    fn high_water_mark() {
+        // the classes peak at different times, so the pool never had both blocks out at once.
+        let pool = TieredPool::new(&[(64, 1), (256, 1)]);
+        drop(pool.acquire_sized(10, "small").expect("small"));
+        drop(pool.acquire_sized(100, "big").expect("big"));
+
+        let stats = pool.stats();
+        assert_eq!(stats.in_use, 0);
+        assert_eq!(stats.high_water_mark, 1);
+        assert_eq!(stats.total_acquires, 2);
+        assert!(stats.owners.is_empty());
+        assert!(pool
+            .class_stats()
+            .iter()
+            .all(|(_, stats)| stats.high_water_mark == 1));
+    }
+
+    #[test]
+    fn waits_for_release() {
+        let pool = TieredPool::new(&[(64, 1), (256, 1)]);
+        let small = pool.acquire_sized(10, "small").expect("small");
+        let big = pool.acquire_sized(10, "small").expect("big");
+
+        let handle = {
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                pool.acquire_sized(10, "waiter")
+                    .expect("waiter")
+                    .unfilled_capacity()
+            })
+        };
+        drop(big);
+        assert_eq!(handle.join().expect("join"), 256);
+        drop(small);
+    }
+
+    #[test]
+    fn decode_with_header_len() {
+        let packet = Packet::Put(Put::new(1, 1, test_key(), binary_data(&[1; 100])));
+        let mut bytes = vec![];
+        packet.encode(&mut bytes).expect("encode");
+        let mut cursor = Cursor::new(bytes);
+
+        let pool = TieredPool::new(&[(16, 1), (128, 1), (1024, 1)]);
+        let header = Header::decode(&mut cursor).expect("header");
+        let mut buffer = pool.acquire_sized(header.len, "decode").expect("buffer");
+        assert_eq!(buffer.unfilled_capacity(), 128);
+
+        let decoded = full_decode(&mut cursor, &mut buffer, Some(header)).expect("decode");
+        assert_eq!(decoded, packet);
+    }
+}
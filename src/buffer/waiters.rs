@@ -0,0 +1,394 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug, Formatter},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+
+use crossbeam_channel::Receiver;
+
+use super::{block::Memory, BufferOwner, OwnedImpl, Pool, PoolImpl, ReturnPath};
+
+#[derive(Debug, Default)]
+struct WaiterState {
+    // the block and the path it goes back to.
+    memory: Option<(Memory, ReturnPath)>,
+    // set once a block is handed over, so the waiter is skipped in the queues of other paths.
+    served: bool,
+    waker: Option<Waker>,
+}
+
+/// A queued [`Acquire`], blocks are handed to it directly so no later acquire can take them.
+///
+/// It may be queued on several return paths, the first to have a block serves it.
+#[derive(Debug, Default)]
+pub(crate) struct Waiter(Mutex<WaiterState>);
+
+impl Waiter {
+    fn new(waker: &Waker) -> Arc<Self> {
+        Arc::new(Self(Mutex::new(WaiterState {
+            memory: None,
+            served: false,
+            waker: Some(waker.clone()),
+        })))
+    }
+}
+
+/// The queue of [`Acquire`] futures waiting for a block, woken in the order they started waiting.
+#[derive(Debug)]
+pub(crate) struct AsyncWaiters {
+    len: AtomicUsize,
+    queue: Mutex<VecDeque<Arc<Waiter>>>,
+    // weak so the blocks out of the pool don't keep its channel connected.
+    receiver: Weak<Receiver<Memory>>,
+}
+
+impl AsyncWaiters {
+    pub fn new(receiver: Weak<Receiver<Memory>>) -> Self {
+        Self {
+            len: AtomicUsize::new(0),
+            queue: Mutex::default(),
+            receiver,
+        }
+    }
+
+    pub fn waiting(&self) -> bool {
+        self.len.load(Ordering::SeqCst) > 0
+    }
+
+    /// Queues a waiter, it may be handed a block straight away.
+    fn register(&self, returns: &ReturnPath, waiter: &Arc<Waiter>) {
+        let mut queue = self.queue.lock().expect("waiters lock");
+        // counted before looking at the channel, so a release that sends after this dispatches.
+        self.len.fetch_add(1, Ordering::SeqCst);
+        if let Some(cache) = &returns.cache {
+            cache.enter(&returns.sender);
+        }
+        queue.push_back(waiter.clone());
+        let wakers = self.dispatch_locked(&mut queue, returns);
+        drop(queue);
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Hands the blocks in the channel to the waiters at the front of the queue.
+    pub fn dispatch(&self, returns: &ReturnPath) {
+        let mut queue = self.queue.lock().expect("waiters lock");
+        let wakers = self.dispatch_locked(&mut queue, returns);
+        drop(queue);
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    fn dispatch_locked(
+        &self,
+        queue: &mut VecDeque<Arc<Waiter>>,
+        returns: &ReturnPath,
+    ) -> Vec<Waker> {
+        let mut wakers = vec![];
+        // nobody is left to wait once the pool is gone.
+        let Some(receiver) = self.receiver.upgrade() else {
+            return wakers;
+        };
+        while let Some(waiter) = queue.front().cloned() {
+            let mut state = waiter.0.lock().expect("waiter lock");
+            // served by another path, checked under the waiter lock so it gets one block only.
+            if !state.served {
+                let Ok(memory) = receiver.try_recv() else {
+                    break;
+                };
+                state.memory = Some((memory, returns.clone()));
+                state.served = true;
+                wakers.extend(state.waker.take());
+            }
+            drop(state);
+
+            queue.pop_front();
+            self.dequeued(returns);
+        }
+        wakers
+    }
+
+    /// Removes a waiter from the queue if it is still in it.
+    fn remove(&self, returns: &ReturnPath, waiter: &Arc<Waiter>) {
+        let mut queue = self.queue.lock().expect("waiters lock");
+        if let Some(index) = queue.iter().position(|queued| Arc::ptr_eq(queued, waiter)) {
+            queue.remove(index);
+            self.dequeued(returns);
+        }
+    }
+
+    fn dequeued(&self, returns: &ReturnPath) {
+        self.len.fetch_sub(1, Ordering::SeqCst);
+        if let Some(cache) = &returns.cache {
+            cache.leave();
+        }
+    }
+}
+
+/// A future that resolves to a buffer once one is available, see [`PoolImpl::acquire_async`].
+///
+/// Dropping it before it resolves gives up its place in the queue, and any block it was handed in
+/// the meantime goes to the next waiter.
+#[must_use = "futures do nothing unless polled"]
+pub struct Acquire<R: BufferOwner> {
+    pool: PoolImpl,
+    reason: R,
+    waiter: Option<(Arc<Waiter>, Instant)>,
+}
+
+impl<R: BufferOwner> Debug for Acquire<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Acquire")
+            .field("reason", &self.reason.why())
+            .field("waiting", &self.waiter.is_some())
+            .finish()
+    }
+}
+
+// the future is never pinned structurally.
+impl<R: BufferOwner> Unpin for Acquire<R> {}
+
+impl PoolImpl {
+    /// Acquires a buffer without blocking the thread, waiting for one to be released if the pool
+    /// is exhausted.
+    ///
+    /// Async waiters are handed released blocks in the order they started waiting. Blocking
+    /// acquires are not part of that queue and can still take a block first. Owners with a
+    /// reservation, see [`PoolImpl::with_reserved`], wait for blocks returning to either the pool
+    /// or the reservation.
+    ///
+    /// The future holds its own handle to the pool, so it can be spawned onto another task.
+    pub fn acquire_async<R: BufferOwner>(&self, reason: R) -> Acquire<R> {
+        Acquire {
+            pool: self.clone(),
+            reason,
+            waiter: None,
+        }
+    }
+}
+
+impl<R: BufferOwner> Future for Acquire<R> {
+    type Output = OwnedImpl;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let Some((waiter, start)) = &this.waiter else {
+            // only skip the queues if nobody is in them.
+            let waiting = this
+                .pool
+                .wait_paths(&this.reason)
+                .any(|returns| returns.waiters.waiting());
+            if !waiting {
+                if let Some(buffer) = this.pool.try_acquire(this.reason) {
+                    return Poll::Ready(buffer);
+                }
+            }
+            let waiter = Waiter::new(cx.waker());
+            for returns in this.pool.wait_paths(&this.reason) {
+                returns.waiters.register(returns, &waiter);
+            }
+            this.waiter = Some((waiter, Instant::now()));
+            return Pin::new(this).poll(cx);
+        };
+
+        let mut state = waiter.0.lock().expect("waiter lock");
+        match state.memory.take() {
+            Some((memory, returns)) => {
+                drop(state);
+                for path in this.pool.wait_paths(&this.reason) {
+                    path.waiters.remove(path, waiter);
+                }
+                let waited = start.elapsed();
+                this.waiter = None;
+                Poll::Ready(this.pool.wrap(memory, &returns, this.reason.why(), waited))
+            }
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<R: BufferOwner> Drop for Acquire<R> {
+    fn drop(&mut self) {
+        if let Some((waiter, _)) = self.waiter.take() {
+            for returns in self.pool.wait_paths(&self.reason) {
+                returns.waiters.remove(returns, &waiter);
+            }
+            // a block handed over in the meantime goes to the next waiter, or is freed if the
+            // pool shrank while it was waiting.
+            let memory = waiter.0.lock().expect("waiter lock").memory.take();
+            if let Some((memory, returns)) = memory {
+                returns.release(memory, self.reason.why());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll, Wake, Waker},
+        thread::{self, Thread},
+        time::Duration,
+    };
+
+    use crate::buffer::{Owned, Pool, PoolImpl, ReservedFor};
+
+    #[derive(Default)]
+    struct CountWaker(AtomicUsize);
+
+    impl Wake for CountWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn acquire_async() {
+        let pool = PoolImpl::new(8, 1);
+        let buffer = block_on(pool.acquire_async("test"));
+        assert_eq!(buffer.unfilled_capacity(), 8);
+
+        // the future doesn't borrow the pool, so it can move to another thread.
+        let waiter = pool.acquire_async("waiter");
+        let handle = thread::spawn(move || block_on(waiter).unfilled_capacity());
+        thread::sleep(Duration::from_millis(10));
+        drop(buffer);
+        assert_eq!(handle.join().expect("join"), 8);
+        assert_eq!(pool.in_use(), 0);
+    }
+
+    #[test]
+    fn wakes_in_order() {
+        let pool = PoolImpl::new(8, 1).with_thread_cache(4);
+        let buffer = pool.acquire("test");
+
+        let wakes = Arc::new(CountWaker::default());
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut first = pin!(pool.acquire_async("first"));
+        let mut second = pin!(pool.acquire_async("second"));
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        drop(buffer);
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        let Poll::Ready(buffer) = first.as_mut().poll(&mut cx) else {
+            panic!("first waiter was not handed the block");
+        };
+        // the block skips the thread cache while someone is waiting.
+        assert_eq!(pool.cached(), 0);
+
+        drop(buffer);
+        let Poll::Ready(_buffer) = second.as_mut().poll(&mut cx) else {
+            panic!("second waiter was not handed the block");
+        };
+        assert_eq!(pool.stats().owners.get("second"), Some(&1));
+    }
+
+    #[test]
+    fn cancel_keeps_blocks() {
+        let pool = PoolImpl::new(8, 1);
+        let buffer = pool.acquire("test");
+
+        let waker = Waker::from(Arc::new(CountWaker::default()));
+        let mut cx = Context::from_waker(&waker);
+        let mut first = Box::pin(pool.acquire_async("first"));
+        let mut second = Box::pin(pool.acquire_async("second"));
+        let mut third = Box::pin(pool.acquire_async("third"));
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert!(third.as_mut().poll(&mut cx).is_pending());
+
+        // cancelled while still queued.
+        drop(first);
+        drop(buffer);
+        // cancelled after being handed the block, which moves on to the next waiter.
+        drop(second);
+        assert!(third.as_mut().poll(&mut cx).is_ready());
+        drop(third);
+
+        assert_eq!(pool.in_use(), 0);
+        assert_eq!(pool.available(), 1);
+        assert!(pool.try_acquire("test").is_some());
+    }
+
+    #[test]
+    fn cancel_after_shrink() {
+        let pool = PoolImpl::new(8, 1);
+        let buffer = pool.acquire("test");
+
+        let waker = Waker::from(Arc::new(CountWaker::default()));
+        let mut cx = Context::from_waker(&waker);
+        let mut waiter = Box::pin(pool.acquire_async("waiter"));
+        assert!(waiter.as_mut().poll(&mut cx).is_pending());
+
+        drop(buffer);
+        assert_eq!(pool.shrink(1), 1);
+        // the block handed to the cancelled waiter is retired rather than kept.
+        drop(waiter);
+        assert_eq!(pool.capacity(), 0);
+        assert!(pool.try_acquire("test").is_none());
+    }
+
+    #[test]
+    fn reserved_waits_on_pool() {
+        let pool = PoolImpl::new(8, 1).with_reserved(1, [ReservedFor::Owner("operator")]);
+        let data = pool.acquire("test");
+        let reserved = pool.acquire("operator");
+
+        let waker = Waker::from(Arc::new(CountWaker::default()));
+        let mut cx = Context::from_waker(&waker);
+        let mut waiter = pin!(pool.acquire_async("operator"));
+        assert!(waiter.as_mut().poll(&mut cx).is_pending());
+
+        // only a block from the rest of the pool comes back.
+        drop(data);
+        let Poll::Ready(buffer) = waiter.as_mut().poll(&mut cx) else {
+            panic!("waiter was not handed the pool's block");
+        };
+        assert_eq!(pool.in_use(), 2);
+
+        // the reservation's block goes back to it, not to the served waiter.
+        drop(reserved);
+        assert!(pool.try_acquire("test").is_none());
+        assert!(pool.try_acquire("operator").is_some());
+        drop(buffer);
+        assert!(pool.try_acquire("test").is_some());
+    }
+}
@@ -0,0 +1,57 @@
+use std::sync::atomic::{compiler_fence, Ordering};
+
+/// How a pool clears blocks when they are released back to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ZeroPolicy {
+    /// Zero the whole block.
+    #[default]
+    Always,
+    /// Zero only the bytes that were marked as filled while the block was acquired.
+    /// Bytes written to the unfilled part of a buffer without calling `fill` are left as is, so
+    /// code that writes to `unfilled` directly must fill or zero what it wrote. Decoding does this
+    /// itself, a read that fails part way zeroes the bytes it wrote.
+    Used,
+    /// Never zero, the next owner may see the previous owner's bytes.
+    Never,
+    /// Zero the whole block with volatile writes the compiler cannot optimize away.
+    /// Meant for pools that hold sensitive values.
+    Secure,
+}
+
+impl ZeroPolicy {
+    /// Zeroes `bytes` according to the policy, `used` is how many leading bytes were filled.
+    pub(crate) fn zero(self, bytes: &mut [u8], used: usize) {
+        match self {
+            ZeroPolicy::Always => bytes.fill(0),
+            ZeroPolicy::Used => {
+                let used = used.min(bytes.len());
+                bytes[..used].fill(0);
+            }
+            ZeroPolicy::Never => {}
+            ZeroPolicy::Secure => {
+                for byte in bytes.iter_mut() {
+                    // SAFETY: `byte` is a valid, aligned and exclusive reference.
+                    unsafe { std::ptr::write_volatile(byte, 0) };
+                }
+                compiler_fence(Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::ZeroPolicy;
+
+    #[test_case(ZeroPolicy::Always, [0, 0, 0, 0]; "always")]
+    #[test_case(ZeroPolicy::Used, [0, 0, 3, 4]; "used")]
+    #[test_case(ZeroPolicy::Never, [1, 2, 3, 4]; "never")]
+    #[test_case(ZeroPolicy::Secure, [0, 0, 0, 0]; "secure")]
+    fn zero(policy: ZeroPolicy, expected: [u8; 4]) {
+        let mut bytes = [1, 2, 3, 4];
+        policy.zero(&mut bytes, 2);
+        assert_eq!(bytes, expected);
+    }
+}
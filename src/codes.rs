@@ -16,5 +16,7 @@ pub const KEY_ALREADY_EXISTS: u8 = 0x16;
 // TODO errors, should be updated later!
 // errors (start at 0xa0)
 pub const FAILED_TO_PUSH_TO_TRANSACTION_LOG: u8 = 0xa0;
+pub const LOG_TRUNCATED: u8 = 0xa1;
 pub const CHAIN_NOT_READY: u8 = 0xb0;
+pub const STALE_EPOCH: u8 = 0xb1;
 pub const INTERNAL_ERROR: u8 = 0xff;
@@ -17,21 +17,56 @@ pub enum Error {
     #[error("encode err: {0}")]
     Encode(#[source] std::io::Error),
 
+    #[error("invalid address: {0}")]
+    InvalidAddress(&'static str),
+
     #[error("invalid header kind: {0}")]
     InvalidHeaderKind(u8),
 
     #[error("invalid header version: {0}")]
     InvalidHeaderVersion(u8),
 
+    #[error("invalid utf8: {0}")]
+    InvalidUtf8(#[source] std::str::Utf8Error),
+
     #[error("io err: {0}")]
     Io(#[from] std::io::Error),
 
     #[error("owned acquire {acquire} > capacity {capacity}")]
     OwnedRemaining { acquire: usize, capacity: usize },
 
+    #[error("pool exhausted acquiring buffer for {owner}")]
+    PoolExhausted { owner: &'static str },
+
+    #[error("bad address: {0}")]
+    SystemBadAddress(u8),
+
     #[error("bad position: {0}")]
     SystemBadPosition(u8),
 
     #[error("bad role: {0}")]
     SystemBadRole(u8),
+
+    #[error("bad slot range: {start}..{end}")]
+    SystemBadSlotRange { start: u16, end: u16 },
+
+    #[error("transfer checksum {actual:#010x} != {expected:#010x}")]
+    TransferChecksum { expected: u32, actual: u32 },
+
+    #[error("transfer last {last} but content ends at {end} of {total}")]
+    TransferLast { last: bool, end: u64, total: u64 },
+
+    #[error("transfer path {path} + content {content} != packet len {len}")]
+    TransferLength {
+        len: usize,
+        path: usize,
+        content: usize,
+    },
+
+    #[error("transfer offset {offset} + content {content} > total {total}")]
+    TransferOffset {
+        offset: u64,
+        content: usize,
+        total: u64,
+    },
 }
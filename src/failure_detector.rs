@@ -0,0 +1,386 @@
+//! A phi accrual failure detector, see "The φ Accrual Failure Detector" (Hayashibara et al.).
+//!
+//! The detector doesn't send anything itself. The operator tells it when it sends a `Ping` to a
+//! node and when the `PingAck` comes back, and asks it how suspicious it is of each node. Every
+//! time is passed in, so it works with any transport and can be tested with a simulated clock.
+//!
+//! The intervals between acks are kept for each node. The longer a node has been silent compared
+//! to those intervals, the higher its phi: a phi of 1 means a 10% chance the node is still alive
+//! and the next ack is late, 2 means 1%, 3 means 0.1% and so on.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+/// How suspicious the detector is of a node.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Suspicion {
+    /// The node is answering pings.
+    Alive,
+    /// The node is late answering pings, it could be overloaded or partitioned.
+    Suspect,
+    /// The node has been silent for so long it should be removed from the chain.
+    Dead,
+}
+
+#[derive(Clone, Debug)]
+pub struct FailureDetector<K>
+where
+    K: Eq + Hash,
+{
+    suspect: f64,
+    dead: f64,
+    window: usize,
+    min_std_dev: Duration,
+    acceptable_pause: Duration,
+    first_interval: Duration,
+    nodes: HashMap<K, Node>,
+}
+
+impl<K> FailureDetector<K>
+where
+    K: Eq + Hash,
+{
+    /// Creates a detector for nodes pinged about every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            suspect: 3.0,
+            dead: 8.0,
+            window: 100,
+            min_std_dev: Duration::from_millis(100),
+            acceptable_pause: Duration::ZERO,
+            first_interval: interval,
+            nodes: HashMap::new(),
+        }
+    }
+
+    /// Sets the phi at which a node is suspect and the phi at which it is dead.
+    pub fn with_thresholds(mut self, suspect: f64, dead: f64) -> Self {
+        assert!(suspect <= dead, "suspect threshold above dead threshold");
+        self.suspect = suspect;
+        self.dead = dead;
+        self
+    }
+
+    /// Sets how many intervals between acks are kept for each node.
+    pub fn with_window(mut self, window: usize) -> Self {
+        assert!(window > 0, "window must not be empty");
+        self.window = window;
+        self
+    }
+
+    /// Sets the smallest standard deviation used, so a node that acks like clockwork isn't
+    /// suspected the moment an ack is a little late.
+    pub fn with_min_std_dev(mut self, min_std_dev: Duration) -> Self {
+        self.min_std_dev = min_std_dev;
+        self
+    }
+
+    /// Sets how long a node can pause, for example for a compaction, on top of the usual interval
+    /// before it becomes suspect.
+    pub fn with_acceptable_pause(mut self, pause: Duration) -> Self {
+        self.acceptable_pause = pause;
+        self
+    }
+
+    /// Records a `Ping` sent to `node` at `now`. The first ping starts watching the node, so a
+    /// node that never acks becomes suspect.
+    pub fn ping_sent(&mut self, node: K, now: Instant) {
+        self.nodes.entry(node).or_insert_with(|| Node::new(now));
+    }
+
+    /// Records a `PingAck` from `node` received at `now`.
+    pub fn ack_received(&mut self, node: K, now: Instant) {
+        let node = self.nodes.entry(node).or_insert_with(|| Node::new(now));
+        match node.last_ack {
+            Some(last) => {
+                let interval = now.saturating_duration_since(last);
+                node.intervals.push(interval, self.window);
+            }
+            None => {
+                // no history yet, start from the ping interval with a wide deviation.
+                let mean = self.first_interval.as_secs_f64() * 1000.0;
+                let std_dev = mean / 4.0;
+                node.intervals.push_millis(mean - std_dev, self.window);
+                node.intervals.push_millis(mean + std_dev, self.window);
+            }
+        }
+        node.last_ack = Some(now);
+        node.heard = now;
+    }
+
+    /// Stops watching `node`, for example once it has left the chain.
+    pub fn remove(&mut self, node: &K) {
+        self.nodes.remove(node);
+    }
+
+    /// Returns the phi of `node` at `now`, or `None` if it isn't being watched.
+    pub fn phi(&self, node: &K, now: Instant) -> Option<f64> {
+        let node = self.nodes.get(node)?;
+        let elapsed = now.saturating_duration_since(node.heard).as_secs_f64() * 1000.0;
+        let (mean, std_dev) = if node.intervals.is_empty() {
+            let mean = self.first_interval.as_secs_f64() * 1000.0;
+            (mean, mean / 4.0)
+        } else {
+            (node.intervals.mean(), node.intervals.std_dev())
+        };
+        let mean = mean + self.acceptable_pause.as_secs_f64() * 1000.0;
+        let std_dev = std_dev.max(self.min_std_dev.as_secs_f64() * 1000.0);
+
+        Some(phi(elapsed, mean, std_dev))
+    }
+
+    /// Returns how suspicious the detector is of `node` at `now`, or `None` if it isn't being
+    /// watched.
+    pub fn suspicion(&self, node: &K, now: Instant) -> Option<Suspicion> {
+        let phi = self.phi(node, now)?;
+        Some(if phi >= self.dead {
+            Suspicion::Dead
+        } else if phi >= self.suspect {
+            Suspicion::Suspect
+        } else {
+            Suspicion::Alive
+        })
+    }
+
+    /// Returns every watched node that is suspect or dead at `now`.
+    pub fn suspects(&self, now: Instant) -> impl Iterator<Item = (&K, Suspicion)> {
+        self.nodes.keys().filter_map(move |node| {
+            self.suspicion(node, now)
+                .filter(|suspicion| *suspicion != Suspicion::Alive)
+                .map(|suspicion| (node, suspicion))
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    /// When the node was last heard from, the first ping until it acks.
+    heard: Instant,
+    last_ack: Option<Instant>,
+    intervals: Intervals,
+}
+
+impl Node {
+    fn new(now: Instant) -> Self {
+        Self {
+            heard: now,
+            last_ack: None,
+            intervals: Intervals::default(),
+        }
+    }
+}
+
+/// A window of intervals in milliseconds, with running sums for the mean and variance.
+#[derive(Clone, Debug, Default)]
+struct Intervals {
+    millis: VecDeque<f64>,
+    sum: f64,
+    squares: f64,
+}
+
+impl Intervals {
+    fn push(&mut self, interval: Duration, window: usize) {
+        self.push_millis(interval.as_secs_f64() * 1000.0, window);
+    }
+
+    fn push_millis(&mut self, millis: f64, window: usize) {
+        while self.millis.len() >= window {
+            if let Some(old) = self.millis.pop_front() {
+                self.sum -= old;
+                self.squares -= old * old;
+            }
+        }
+        self.millis.push_back(millis);
+        self.sum += millis;
+        self.squares += millis * millis;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.millis.is_empty()
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.millis.len() as f64
+    }
+
+    fn std_dev(&self) -> f64 {
+        let mean = self.mean();
+        (self.squares / self.millis.len() as f64 - mean * mean)
+            .max(0.0)
+            .sqrt()
+    }
+}
+
+/// Returns -log10 of the chance an ack is still to come after `elapsed`, using a logistic
+/// approximation of the normal distribution.
+fn phi(elapsed: f64, mean: f64, std_dev: f64) -> f64 {
+    let y = (elapsed - mean) / std_dev;
+    let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+    if elapsed > mean {
+        -(e / (1.0 + e)).log10()
+    } else {
+        -(1.0 - 1.0 / (1.0 + e)).log10()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{FailureDetector, Suspicion};
+
+    /// A clock that only moves when a test advances it.
+    struct Clock {
+        now: Instant,
+    }
+
+    impl Clock {
+        fn new() -> Self {
+            Self {
+                now: Instant::now(),
+            }
+        }
+
+        fn advance(&mut self, by: Duration) -> Instant {
+            self.now += by;
+            self.now
+        }
+    }
+
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Pings `node` every interval and acks after `rtt`, `rounds` times.
+    fn heartbeat(
+        detector: &mut FailureDetector<&'static str>,
+        clock: &mut Clock,
+        node: &'static str,
+        rtt: Duration,
+        rounds: usize,
+    ) {
+        for _ in 0..rounds {
+            detector.ping_sent(node, clock.now);
+            detector.ack_received(node, clock.advance(rtt));
+            clock.advance(INTERVAL - rtt);
+        }
+    }
+
+    #[test]
+    fn healthy() {
+        let mut clock = Clock::new();
+        let mut detector = FailureDetector::new(INTERVAL);
+        heartbeat(&mut detector, &mut clock, "a", Duration::from_millis(5), 20);
+
+        assert!(detector.phi(&"a", clock.now).unwrap() < 1.0);
+        assert_eq!(detector.suspicion(&"a", clock.now), Some(Suspicion::Alive));
+        assert_eq!(detector.suspects(clock.now).count(), 0);
+        assert_eq!(detector.suspicion(&"b", clock.now), None);
+    }
+
+    #[test]
+    fn silent() {
+        let mut clock = Clock::new();
+        let mut detector = FailureDetector::new(INTERVAL);
+        heartbeat(&mut detector, &mut clock, "a", Duration::from_millis(5), 20);
+
+        // phi only grows while the node is silent.
+        let mut last = 0.0;
+        let mut seen = vec![];
+        for _ in 0..40 {
+            detector.ping_sent("a", clock.now);
+            let now = clock.advance(Duration::from_millis(100));
+            let phi = detector.phi(&"a", now).unwrap();
+            assert!(phi >= last);
+            last = phi;
+            let suspicion = detector.suspicion(&"a", now).unwrap();
+            if seen.last() != Some(&suspicion) {
+                seen.push(suspicion);
+            }
+        }
+        assert_eq!(
+            seen,
+            [Suspicion::Alive, Suspicion::Suspect, Suspicion::Dead]
+        );
+        assert_eq!(
+            detector.suspects(clock.now).collect::<Vec<_>>(),
+            [(&"a", Suspicion::Dead)]
+        );
+
+        // an ack clears the suspicion.
+        detector.ack_received("a", clock.now);
+        assert_eq!(detector.suspicion(&"a", clock.now), Some(Suspicion::Alive));
+
+        detector.remove(&"a");
+        assert_eq!(detector.phi(&"a", clock.now), None);
+    }
+
+    #[test]
+    fn never_acked() {
+        let mut clock = Clock::new();
+        let mut detector = FailureDetector::new(INTERVAL);
+        detector.ping_sent("a", clock.now);
+        assert_eq!(detector.suspicion(&"a", clock.now), Some(Suspicion::Alive));
+
+        // later pings don't reset the time the node was first pinged.
+        for _ in 0..5 {
+            detector.ping_sent("a", clock.advance(INTERVAL));
+        }
+        assert_eq!(detector.suspicion(&"a", clock.now), Some(Suspicion::Dead));
+    }
+
+    #[test]
+    fn jitter() {
+        // a node that acks irregularly is given longer before it is suspect.
+        let mut clock = Clock::new();
+        let mut detector = FailureDetector::new(INTERVAL);
+        for i in 0..50 {
+            heartbeat(
+                &mut detector,
+                &mut clock,
+                "steady",
+                Duration::from_millis(5),
+                1,
+            );
+            let rtt = Duration::from_millis(if i % 2 == 0 { 5 } else { 900 });
+            heartbeat(&mut detector, &mut clock, "jittery", rtt, 1);
+        }
+
+        let now = clock.advance(Duration::from_millis(1500));
+        assert!(detector.phi(&"steady", now).unwrap() > detector.phi(&"jittery", now).unwrap());
+    }
+
+    #[test]
+    fn acceptable_pause() {
+        let mut clock = Clock::new();
+        let mut strict = FailureDetector::new(INTERVAL);
+        let mut lenient =
+            FailureDetector::new(INTERVAL).with_acceptable_pause(Duration::from_secs(3));
+        for _ in 0..10 {
+            strict.ping_sent("a", clock.now);
+            lenient.ping_sent("a", clock.now);
+            let now = clock.advance(Duration::from_millis(5));
+            strict.ack_received("a", now);
+            lenient.ack_received("a", now);
+            clock.advance(INTERVAL - Duration::from_millis(5));
+        }
+
+        let now = clock.advance(Duration::from_secs(3));
+        assert_eq!(strict.suspicion(&"a", now), Some(Suspicion::Dead));
+        assert_eq!(lenient.suspicion(&"a", now), Some(Suspicion::Alive));
+    }
+
+    #[test]
+    fn window() {
+        let mut clock = Clock::new();
+        let mut detector = FailureDetector::new(INTERVAL).with_window(4);
+        heartbeat(&mut detector, &mut clock, "a", Duration::from_millis(5), 10);
+
+        // only the last 4 intervals count, which are all exactly the ping interval.
+        let intervals = &detector.nodes[&"a"].intervals;
+        assert_eq!(intervals.millis.len(), 4);
+        assert!((intervals.mean() - 1000.0).abs() < 1e-6);
+        assert!(intervals.std_dev() < 1e-3);
+    }
+}
@@ -115,6 +115,16 @@ where
 {
     fn decode(reader: &mut R) -> Result<Self, Error> {
         let kind = Kind::decode(reader)?;
+        Self::decode_after_kind(kind, reader)
+    }
+}
+
+impl Header {
+    /// Decodes the rest of a header whose kind has already been read.
+    pub(crate) fn decode_after_kind<R>(kind: Kind, reader: &mut R) -> Result<Self, Error>
+    where
+        R: Read,
+    {
         let version = Version::decode(reader)?;
         let len = usize::decode(reader)?;
         let uuid = Uuid::decode(reader)?;
@@ -43,7 +43,36 @@ pub enum Kind {
     Transfer,
     TransferAck,
     Ping,
-    PingAck = system_codec::END as isize,
+    PingAck,
+    TransferBegin,
+    TransferBeginAck,
+    TransferEnd,
+    TransferEndAck,
+    TransferProgress,
+    CatchUpRequest,
+    CatchUp,
+    Leave,
+    LeaveAck = system_codec::END as isize,
+}
+
+/// The codec a [`Kind`] of packet belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Codec {
+    Deque,
+    KvStore,
+    System,
+}
+
+impl Kind {
+    /// Returns the codec the packet belongs to.
+    pub fn codec(self) -> Codec {
+        match u8::from(self) {
+            deque_codec::START..=deque_codec::END => Codec::Deque,
+            kv_store_codec::START..=kv_store_codec::END => Codec::KvStore,
+            system_codec::START..=system_codec::END => Codec::System,
+            value => unreachable!("kind {} is outside every codec", value),
+        }
+    }
 }
 
 impl Debug for Kind {
@@ -80,6 +109,15 @@ impl Debug for Kind {
             Self::TransferAck => write!(f, "TransferAck"),
             Self::Ping => write!(f, "Ping"),
             Self::PingAck => write!(f, "PingAck"),
+            Self::TransferBegin => write!(f, "TransferBegin"),
+            Self::TransferBeginAck => write!(f, "TransferBeginAck"),
+            Self::TransferEnd => write!(f, "TransferEnd"),
+            Self::TransferEndAck => write!(f, "TransferEndAck"),
+            Self::TransferProgress => write!(f, "TransferProgress"),
+            Self::CatchUpRequest => write!(f, "CatchUpRequest"),
+            Self::CatchUp => write!(f, "CatchUp"),
+            Self::Leave => write!(f, "Leave"),
+            Self::LeaveAck => write!(f, "LeaveAck"),
         };
 
         res?;
@@ -140,6 +178,15 @@ impl From<u8> for Kind {
             system_codec::TRANSFER_ACK => Kind::TransferAck,
             system_codec::PING => Kind::Ping,
             system_codec::PING_ACK => Kind::PingAck,
+            system_codec::TRANSFER_BEGIN => Kind::TransferBegin,
+            system_codec::TRANSFER_BEGIN_ACK => Kind::TransferBeginAck,
+            system_codec::TRANSFER_END => Kind::TransferEnd,
+            system_codec::TRANSFER_END_ACK => Kind::TransferEndAck,
+            system_codec::TRANSFER_PROGRESS => Kind::TransferProgress,
+            system_codec::CATCH_UP_REQUEST => Kind::CatchUpRequest,
+            system_codec::CATCH_UP => Kind::CatchUp,
+            system_codec::LEAVE => Kind::Leave,
+            system_codec::LEAVE_ACK => Kind::LeaveAck,
 
             _ => panic!("invalid kind: {}", value),
         }
@@ -180,6 +227,15 @@ impl From<Kind> for u8 {
             Kind::TransferAck => system_codec::TRANSFER_ACK,
             Kind::Ping => system_codec::PING,
             Kind::PingAck => system_codec::PING_ACK,
+            Kind::TransferBegin => system_codec::TRANSFER_BEGIN,
+            Kind::TransferBeginAck => system_codec::TRANSFER_BEGIN_ACK,
+            Kind::TransferEnd => system_codec::TRANSFER_END,
+            Kind::TransferEndAck => system_codec::TRANSFER_END_ACK,
+            Kind::TransferProgress => system_codec::TRANSFER_PROGRESS,
+            Kind::CatchUpRequest => system_codec::CATCH_UP_REQUEST,
+            Kind::CatchUp => system_codec::CATCH_UP,
+            Kind::Leave => system_codec::LEAVE,
+            Kind::LeaveAck => system_codec::LEAVE_ACK,
         }
     }
 }
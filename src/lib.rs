@@ -5,17 +5,21 @@ use std::io::{Read, Write};
 use log::{debug, trace};
 
 mod buffer;
+#[cfg(feature = "async")]
+pub use buffer::Acquire;
 #[cfg(any(test, feature = "test"))]
 pub use buffer::{binary_data, byte_str};
 pub use buffer::{
-    fill, BinaryData, BufferOwner, ByteStr, Owned, OwnedImpl, Pool, PoolImpl, Shared, SharedImpl,
+    fill, BinaryData, BufferOwner, ByteStr, ChainedOwned, OutstandingBuffer, Owned, OwnedImpl,
+    Pool, PoolImpl, PoolStats, ReservedFor, RopeReader, Shared, SharedImpl, SharedRope, TieredPool,
+    VecOwned, ZeroPolicy, DEFAULT_VEC_OWNED_LIMIT,
 };
 
 mod codes;
 pub use codes::{
     CHAIN_NOT_READY, FAILED_TO_PUSH_TO_TRANSACTION_LOG, INTERNAL_ERROR, KEY_ALREADY_EXISTS,
-    KEY_DOES_NOT_EXIST, QUEUE_ALREADY_EXISTS, QUEUE_DOES_NOT_EXIST, QUEUE_EMPTY, QUEUE_FULL,
-    SERVER_BUSY, SUCCESS,
+    KEY_DOES_NOT_EXIST, LOG_TRUNCATED, QUEUE_ALREADY_EXISTS, QUEUE_DOES_NOT_EXIST, QUEUE_EMPTY,
+    QUEUE_FULL, SERVER_BUSY, STALE_EPOCH, SUCCESS,
 };
 
 pub mod deque_codec;
@@ -27,11 +31,13 @@ use deque_codec::{
 mod error;
 pub use error::Error;
 
+pub mod failure_detector;
+
 mod header;
 pub use header::{Header, Uuid, Version};
 
 mod kind;
-pub use kind::Kind;
+pub use kind::{Codec, Kind};
 
 pub mod kv_store_codec;
 use kv_store_codec::{Delete, DeleteAck, Get, GetAck, Put, PutAck};
@@ -40,7 +46,11 @@ pub mod response;
 pub use response::Response;
 
 pub mod system_codec;
-use system_codec::{Join, JoinAck, Ping, PingAck, Report, ReportAck, Transfer, TransferAck};
+use system_codec::{
+    slot_of, CatchUp, CatchUpRequest, Join, JoinAck, Leave, LeaveAck, Ping, PingAck, Report,
+    ReportAck, Transfer, TransferAck, TransferBegin, TransferBeginAck, TransferEnd, TransferEndAck,
+    TransferProgress,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Packet<S>
@@ -78,6 +88,15 @@ where
     TransferAck(TransferAck<S>),
     Ping(Ping<S>),
     PingAck(PingAck<S>),
+    TransferBegin(TransferBegin<S>),
+    TransferBeginAck(TransferBeginAck<S>),
+    TransferEnd(TransferEnd<S>),
+    TransferEndAck(TransferEndAck<S>),
+    TransferProgress(TransferProgress<S>),
+    CatchUpRequest(CatchUpRequest<S>),
+    CatchUp(CatchUp<S>),
+    Leave(Leave<S>),
+    LeaveAck(LeaveAck<S>),
 }
 
 impl<S> Packet<S>
@@ -117,9 +136,40 @@ where
             Packet::TransferAck(packet) => packet.header,
             Packet::Ping(packet) => packet.header,
             Packet::PingAck(packet) => packet.header,
+            Packet::TransferBegin(packet) => packet.header,
+            Packet::TransferBeginAck(packet) => packet.header,
+            Packet::TransferEnd(packet) => packet.header,
+            Packet::TransferEndAck(packet) => packet.header,
+            Packet::TransferProgress(packet) => packet.header,
+            Packet::CatchUpRequest(packet) => packet.header,
+            Packet::CatchUp(packet) => packet.header,
+            Packet::Leave(packet) => packet.header,
+            Packet::LeaveAck(packet) => packet.header,
         }
     }
 
+    /// Returns the hash slot of the key or queue path of a request, which decides the shard that
+    /// serves it. Other packets have no slot.
+    pub fn slot(&self) -> Option<u16> {
+        let data = match self {
+            // deque
+            Packet::Enqueue(packet) => packet.path.data(),
+            Packet::Dequeue(packet) => packet.path.data(),
+            Packet::Peek(packet) => packet.path.data(),
+            Packet::Len(packet) => packet.path.data(),
+            Packet::CreateQueue(packet) => packet.path.data(),
+            Packet::DeleteQueue(packet) => packet.path.data(),
+
+            // kv store
+            Packet::Put(packet) => packet.key.data(),
+            Packet::Get(packet) => packet.key.data(),
+            Packet::Delete(packet) => packet.key.data(),
+
+            _ => return None,
+        };
+        Some(slot_of(data.chunks()))
+    }
+
     pub fn nack(self, response_code: u8, reason: Option<ByteStr<S>>) -> Option<Self> {
         match self {
             // deque
@@ -143,6 +193,14 @@ where
             Packet::Report(this) => Some(Packet::ReportAck(this.nack(response_code, reason))),
             Packet::Join(this) => Some(Packet::JoinAck(this.nack(response_code, reason))),
             Packet::Transfer(this) => Some(Packet::TransferAck(this.nack(response_code, reason))),
+            Packet::TransferBegin(this) => {
+                Some(Packet::TransferBeginAck(this.nack(response_code, reason)))
+            }
+            Packet::TransferEnd(this) => {
+                Some(Packet::TransferEndAck(this.nack(response_code, reason)))
+            }
+            Packet::CatchUpRequest(this) => Some(Packet::CatchUp(this.nack(response_code, reason))),
+            Packet::Leave(this) => Some(Packet::LeaveAck(this.nack(response_code, reason))),
 
             // acks
             _ => None,
@@ -215,6 +273,25 @@ where
         Kind::TransferAck => Packet::TransferAck(TransferAck::decode(header, reader, buffer)?),
         Kind::Ping => Packet::Ping(Ping::decode(header, reader, buffer)?),
         Kind::PingAck => Packet::PingAck(PingAck::decode(header, reader, buffer)?),
+        Kind::TransferBegin => {
+            Packet::TransferBegin(TransferBegin::decode(header, reader, buffer)?)
+        }
+        Kind::TransferBeginAck => {
+            Packet::TransferBeginAck(TransferBeginAck::decode(header, reader, buffer)?)
+        }
+        Kind::TransferEnd => Packet::TransferEnd(TransferEnd::decode(header, reader, buffer)?),
+        Kind::TransferEndAck => {
+            Packet::TransferEndAck(TransferEndAck::decode(header, reader, buffer)?)
+        }
+        Kind::TransferProgress => {
+            Packet::TransferProgress(TransferProgress::decode(header, reader, buffer)?)
+        }
+        Kind::CatchUpRequest => {
+            Packet::CatchUpRequest(CatchUpRequest::decode(header, reader, buffer)?)
+        }
+        Kind::CatchUp => Packet::CatchUp(CatchUp::decode(header, reader, buffer)?),
+        Kind::Leave => Packet::Leave(Leave::decode(header, reader, buffer)?),
+        Kind::LeaveAck => Packet::LeaveAck(LeaveAck::decode(header, reader, buffer)?),
     };
 
     Ok(packet)
@@ -254,6 +331,16 @@ where
         Header::decode(reader)?
     };
 
+    if let Err(err) = buffer.reserve(header.len) {
+        return Err(match err {
+            Error::OwnedRemaining { capacity, .. } => Error::BufferTooSmallForPacketDecode {
+                header,
+                size: header.len,
+                capacity,
+            },
+            err => err,
+        });
+    }
     if header.len > buffer.unfilled_capacity() {
         return Err(Error::BufferTooSmallForPacketDecode {
             header,
@@ -333,8 +420,7 @@ where
 {
     let written = writer.write(bytes).map_err(Error::Encode)?;
     if written != bytes.len() {
-        return Err(Error::Encode(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        return Err(Error::Encode(std::io::Error::other(
             "failed to write all bytes",
         )));
     }
@@ -387,6 +473,15 @@ mod packet {
                 Packet::TransferAck(packet) => packet.encode(writer),
                 Packet::Ping(packet) => packet.encode(writer),
                 Packet::PingAck(packet) => packet.encode(writer),
+                Packet::TransferBegin(packet) => packet.encode(writer),
+                Packet::TransferBeginAck(packet) => packet.encode(writer),
+                Packet::TransferEnd(packet) => packet.encode(writer),
+                Packet::TransferEndAck(packet) => packet.encode(writer),
+                Packet::TransferProgress(packet) => packet.encode(writer),
+                Packet::CatchUpRequest(packet) => packet.encode(writer),
+                Packet::CatchUp(packet) => packet.encode(writer),
+                Packet::Leave(packet) => packet.encode(writer),
+                Packet::LeaveAck(packet) => packet.encode(writer),
             }
         }
     }
@@ -432,8 +527,7 @@ mod integer {
                             .write(&data)
                             .map_err(Error::Encode)?;
                         if bytes != data.len() {
-                            return Err(Error::Encode(std::io::Error::new(
-                                std::io::ErrorKind::Other,
+                            return Err(Error::Encode(std::io::Error::other(
                                 "failed to write all bytes",
                             )));
                         }
@@ -534,6 +628,10 @@ mod vector {
 
     use crate::{buffer::Owned, Decode, DecodeOwned, Encode, Error};
 
+    /// The most elements allocated up front, the length comes off the wire so anything past this
+    /// is only allocated as the elements are actually read.
+    const PREALLOCATE: usize = 64;
+
     impl<R, T> Decode<R> for Vec<T>
     where
         R: Read,
@@ -544,7 +642,7 @@ mod vector {
             Self: Sized,
         {
             let len = usize::decode(reader)?;
-            let mut vec = Vec::with_capacity(len);
+            let mut vec = Vec::with_capacity(len.min(PREALLOCATE));
             for _ in 0..len {
                 vec.push(T::decode(reader)?);
             }
@@ -564,7 +662,7 @@ mod vector {
             Self: Sized,
         {
             let len = usize::decode(reader)?;
-            let mut vec = Vec::with_capacity(len);
+            let mut vec = Vec::with_capacity(len.min(PREALLOCATE));
             for _ in 0..len {
                 vec.push(T::decode_owned(reader, buffer)?);
             }
@@ -645,7 +743,7 @@ pub(crate) mod tests {
 
     #[test_case::test_case(vec![byte_str(b"kittens")]; "vec")]
     #[test_case::test_case(Some(byte_str(b"data")); "option")]
-    #[test_case::test_case(vec![Role::Backend(byte_str(b"test")), Role::Observer]; "role")]
+    #[test_case::test_case(vec![Role::Backend(test_address(b"test")), Role::Observer]; "role")]
     fn encode_decode_owned<T>(val: T)
     where
         T: DecodeOwned<Cursor<Vec<u8>>, OwnedImpl> + Encode<Vec<u8>> + Debug + PartialEq,
@@ -710,27 +808,75 @@ pub(crate) mod tests {
             Packet::Report(Report::new(
                 123,
                 456,
+                3,
+                Some(Shard::new(1, SlotRange::new(0, 8192))),
                 Position::Middle {
-                    next: byte_str(b"next"),
+                    next: test_address(b"next"),
                 },
             )),
             Packet::ReportAck(ReportAck::new(Response::success())),
-            Packet::Join(Join::new(
-                123,
-                456,
-                Role::Backend(byte_str(b"backend")),
-                1,
-                false,
-            )),
-            Packet::JoinAck(JoinAck::new(Response::success(), 1)),
+            Packet::Join(
+                Join::new(
+                    123,
+                    456,
+                    Role::Backend(test_address(b"backend")),
+                    1,
+                    false,
+                    3,
+                )
+                .with_metadata(NodeMetadata::new().with_zone(byte_str(b"us-east-1a"))),
+            ),
+            Packet::JoinAck(JoinAck::new(Response::success(), 1, 3)),
             Packet::Transfer(Transfer::new(
                 123,
                 456,
+                7,
                 byte_str(b"/tmp/kitties"),
                 42,
+                45,
+                7,
                 binary_data(&[1, 2, 3]),
             )),
-            Packet::TransferAck(TransferAck::new(Response::success())),
+            Packet::TransferAck(TransferAck::new(Response::success(), 45)),
+            Packet::TransferBegin(TransferBegin::new(
+                123,
+                456,
+                7,
+                vec![ManifestEntry::new(
+                    byte_str(b"/tmp/kitties"),
+                    45,
+                    0xdeadbeef,
+                )],
+            )),
+            Packet::TransferBeginAck(TransferBeginAck::new(Response::success(), 7)),
+            Packet::TransferEnd(TransferEnd::new(123, 456, 7)),
+            Packet::TransferEndAck(TransferEndAck::new(Response::success(), 7)),
+            Packet::TransferProgress(TransferProgress::new(
+                123,
+                456,
+                test_address(b"candidate"),
+                7,
+                3,
+                45,
+                None,
+            )),
+            Packet::CatchUpRequest(CatchUpRequest::new(123, 456, 7)),
+            Packet::CatchUp(CatchUp::new_test(
+                8,
+                Some(Mutation::Put(crate::kv_store_codec::Put::new(
+                    123,
+                    457,
+                    binary_data(b"kitty"),
+                    binary_data(b"meow"),
+                ))),
+            )),
+            Packet::Leave(Leave::new(
+                123,
+                456,
+                Role::Backend(test_address(b"backend")),
+                2,
+            )),
+            Packet::LeaveAck(LeaveAck::new(Response::success(), 1)),
         ]
     }
 }
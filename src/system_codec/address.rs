@@ -0,0 +1,309 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs},
+    vec,
+};
+
+use crate::{
+    buffer::{ByteStr, Owned, Shared},
+    Decode, DecodeOwned, Encode, Error,
+};
+
+/// The longest path a unix socket can bind to on Linux, where `sun_path` holds 108 bytes
+/// including the nul. Other platforms are shorter, 104 bytes on macOS and the BSDs, so a path that
+/// passes `validate` can still be too long to bind there.
+const MAX_UNIX_PATH: usize = 107;
+
+/// The address of a node, checked when it is decoded so a bad one is rejected with the `Report`
+/// instead of failing to connect later.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address<S>
+where
+    S: Shared,
+{
+    V4(SocketAddrV4),                    // 1
+    V6(SocketAddrV6),                    // 2
+    Dns { host: ByteStr<S>, port: u16 }, // 3
+    Unix(ByteStr<S>),                    // 4
+}
+
+impl<S> Address<S>
+where
+    S: Shared,
+{
+    pub fn encode_len(&self) -> usize {
+        match self {
+            Address::V4(_) | Address::V6(_) => 0,
+            Address::Dns { host, .. } => host.len(),
+            Address::Unix(path) => path.len(),
+        }
+    }
+
+    /// Returns the socket address of an IP address, without resolving DNS names.
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Address::V4(addr) => Some(SocketAddr::V4(*addr)),
+            Address::V6(addr) => Some(SocketAddr::V6(*addr)),
+            Address::Dns { .. } | Address::Unix(_) => None,
+        }
+    }
+
+    /// Checks the address is one a node could connect to.
+    pub fn validate(&self) -> Result<(), Error> {
+        match self {
+            Address::V4(addr) if addr.port() == 0 => Err(Error::InvalidAddress("port 0")),
+            Address::V6(addr) if addr.port() == 0 => Err(Error::InvalidAddress("port 0")),
+            Address::Dns { port: 0, .. } => Err(Error::InvalidAddress("port 0")),
+            Address::Dns { host, .. } if !is_hostname(host.as_slice()) => {
+                Err(Error::InvalidAddress("bad hostname"))
+            }
+            Address::Unix(path) if path.is_empty() || path.len() > MAX_UNIX_PATH => {
+                Err(Error::InvalidAddress("bad unix socket path length"))
+            }
+            Address::Unix(path) if path.as_slice().contains(&0) => {
+                Err(Error::InvalidAddress("nul in unix socket path"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S> From<SocketAddr> for Address<S>
+where
+    S: Shared,
+{
+    fn from(addr: SocketAddr) -> Self {
+        match addr {
+            SocketAddr::V4(addr) => Address::V4(addr),
+            SocketAddr::V6(addr) => Address::V6(addr),
+        }
+    }
+}
+
+impl<S> ToSocketAddrs for Address<S>
+where
+    S: Shared,
+{
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    /// Resolves DNS names, unix sockets have no socket address.
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        match self {
+            Address::V4(addr) => Ok(vec![SocketAddr::V4(*addr)].into_iter()),
+            Address::V6(addr) => Ok(vec![SocketAddr::V6(*addr)].into_iter()),
+            Address::Dns { host, port } => {
+                let host = host
+                    .as_str()
+                    .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err))?;
+                Ok((host, *port)
+                    .to_socket_addrs()?
+                    .collect::<Vec<_>>()
+                    .into_iter())
+            }
+            Address::Unix(_) => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "unix socket has no socket address",
+            )),
+        }
+    }
+}
+
+impl<W, S> Encode<W> for Address<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        match self {
+            Address::V4(addr) => {
+                1u8.encode(writer)?;
+                u32::from(*addr.ip()).encode(writer)?;
+                addr.port().encode(writer)?;
+            }
+            Address::V6(addr) => {
+                2u8.encode(writer)?;
+                u128::from(*addr.ip()).encode(writer)?;
+                addr.port().encode(writer)?;
+                // the scope id picks the interface of a link-local address like `fe80::1%eth0`.
+                addr.flowinfo().encode(writer)?;
+                addr.scope_id().encode(writer)?;
+            }
+            Address::Dns { host, port } => {
+                3u8.encode(writer)?;
+                host.encode(writer)?;
+                port.encode(writer)?;
+            }
+            Address::Unix(path) => {
+                4u8.encode(writer)?;
+                path.encode(writer)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R, O> DecodeOwned<R, O> for Address<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode_owned(reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let kind = u8::decode(reader)?;
+        let address = match kind {
+            1 => {
+                let ip = Ipv4Addr::from(u32::decode(reader)?);
+                let port = u16::decode(reader)?;
+                Address::V4(SocketAddrV4::new(ip, port))
+            }
+            2 => {
+                let ip = Ipv6Addr::from(u128::decode(reader)?);
+                let port = u16::decode(reader)?;
+                let flowinfo = u32::decode(reader)?;
+                let scope_id = u32::decode(reader)?;
+                Address::V6(SocketAddrV6::new(ip, port, flowinfo, scope_id))
+            }
+            3 => {
+                let host = ByteStr::decode_owned(reader, buffer)?;
+                let port = u16::decode(reader)?;
+                Address::Dns { host, port }
+            }
+            4 => Address::Unix(ByteStr::decode_owned(reader, buffer)?),
+            _ => return Err(Error::SystemBadAddress(kind)),
+        };
+        address.validate()?;
+
+        Ok(address)
+    }
+}
+
+/// Checks `host` is a hostname as in RFC 1123: dot separated labels of letters, digits and
+/// hyphens that don't start or end with a hyphen.
+fn is_hostname(host: &[u8]) -> bool {
+    let host = host.strip_suffix(b".").unwrap_or(host);
+    !host.is_empty()
+        && host.len() <= 253
+        && host.split(|b| *b == b'.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && label.first() != Some(&b'-')
+                && label.last() != Some(&b'-')
+                && label
+                    .iter()
+                    .all(|b| b.is_ascii_alphanumeric() || *b == b'-')
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs};
+
+    use matches::assert_matches;
+
+    use crate::{
+        buffer::{byte_str, Pool, PoolImpl},
+        DecodeOwned, Encode, Error, SharedImpl,
+    };
+
+    use super::Address;
+
+    fn decode(address: &Address<SharedImpl>) -> Result<Address<SharedImpl>, Error> {
+        let mut bytes = Vec::new();
+        address.encode(&mut bytes).unwrap();
+
+        let pool = PoolImpl::new(1024, 1);
+        let mut buffer = pool.acquire("decode address");
+        Address::decode_owned(&mut bytes.as_slice(), &mut buffer)
+    }
+
+    #[test]
+    fn encode_decode() {
+        for address in [
+            Address::from("127.0.0.1:8080".parse::<SocketAddr>().unwrap()),
+            Address::from("[::1]:8080".parse::<SocketAddr>().unwrap()),
+            Address::V6(SocketAddrV6::new(
+                Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1),
+                8080,
+                7,
+                2,
+            )),
+            Address::Dns {
+                host: byte_str(b"node-1.chain.local"),
+                port: 8080,
+            },
+            Address::Unix(byte_str(b"/tmp/node.sock")),
+        ] {
+            assert_eq!(decode(&address).unwrap(), address);
+        }
+
+        let pool = PoolImpl::new(1024, 1);
+        let mut buffer = pool.acquire("decode address");
+        assert_matches!(
+            Address::decode_owned(&mut [0u8].as_ref(), &mut buffer),
+            Err(Error::SystemBadAddress(0))
+        );
+    }
+
+    #[test]
+    fn validate() {
+        for address in [
+            Address::from("127.0.0.1:0".parse::<SocketAddr>().unwrap()),
+            Address::Dns {
+                host: byte_str(b"localhost"),
+                port: 0,
+            },
+            Address::Dns {
+                host: byte_str(b""),
+                port: 8080,
+            },
+            Address::Dns {
+                host: byte_str(b"-node.local"),
+                port: 8080,
+            },
+            Address::Dns {
+                host: byte_str(b"node..local"),
+                port: 8080,
+            },
+            Address::Dns {
+                host: byte_str(b"node_1"),
+                port: 8080,
+            },
+            Address::Unix(byte_str(b"")),
+            Address::Unix(byte_str(&[b'a'; 108])),
+            Address::Unix(byte_str(b"/tmp/\0")),
+        ] {
+            assert_matches!(decode(&address), Err(Error::InvalidAddress(_)));
+        }
+
+        assert!(Address::Dns {
+            host: byte_str(b"localhost."),
+            port: 8080,
+        }
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn socket_addrs() {
+        let addr = "127.0.0.1:8080".parse::<SocketAddr>().unwrap();
+        let address = Address::<SharedImpl>::from(addr);
+        assert_eq!(address.socket_addr(), Some(addr));
+        assert_eq!(
+            address.to_socket_addrs().unwrap().collect::<Vec<_>>(),
+            [addr]
+        );
+
+        let unix = Address::Unix(byte_str(b"/tmp/node.sock"));
+        assert_eq!(unix.socket_addr(), None);
+        assert!(unix.to_socket_addrs().is_err());
+
+        let dns = Address::Dns {
+            host: byte_str(b"localhost"),
+            port: 8080,
+        };
+        assert_eq!(dns.socket_addr(), None);
+    }
+}
@@ -0,0 +1,262 @@
+use std::io::{Read, Write};
+
+use crate::{
+    buffer::{Owned, Shared},
+    deque_codec::{self, Enqueue},
+    kv_store_codec::{self, Delete, Put},
+    Ack, Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response,
+};
+
+/// A mutation the `Tail` replays to a catching up `Candidate`, encoded as the packet that made it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Mutation<S>
+where
+    S: Shared,
+{
+    Put(Put<S>),
+    Delete(Delete<S>),
+    Enqueue(Enqueue<S>),
+}
+
+impl<S> Mutation<S>
+where
+    S: Shared,
+{
+    pub fn header(&self) -> Header {
+        match self {
+            Mutation::Put(put) => put.header,
+            Mutation::Delete(delete) => delete.header,
+            Mutation::Enqueue(enqueue) => enqueue.header,
+        }
+    }
+}
+
+impl<W, S> Encode<W> for Mutation<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        match self {
+            Mutation::Put(put) => put.encode(writer),
+            Mutation::Delete(delete) => delete.encode(writer),
+            Mutation::Enqueue(enqueue) => enqueue.encode(writer),
+        }
+    }
+}
+
+impl<R, O> DecodeOwned<R, O> for Mutation<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode_owned(reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        // the kind is checked as a byte, turning an unknown one into a `Kind` panics.
+        match u8::decode(reader)? {
+            kv_store_codec::PUT => {
+                let header = Header::decode_after_kind(Kind::Put, reader)?;
+                Ok(Mutation::Put(Put::decode(header, reader, buffer)?))
+            }
+            kv_store_codec::DELETE => {
+                let header = Header::decode_after_kind(Kind::Delete, reader)?;
+                Ok(Mutation::Delete(Delete::decode(header, reader, buffer)?))
+            }
+            deque_codec::ENQUEUE => {
+                let header = Header::decode_after_kind(Kind::Enqueue, reader)?;
+                Ok(Mutation::Enqueue(Enqueue::decode(header, reader, buffer)?))
+            }
+            kind => Err(Error::InvalidHeaderKind(kind)),
+        }
+    }
+}
+
+/// Streamed from the `Tail` in reply to a [`CatchUpRequest`](super::CatchUpRequest), one mutation
+/// at a time in the order they were applied. The last one has no mutation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct CatchUp<S>
+where
+    S: Shared,
+{
+    pub(crate) header: Header,
+    pub(crate) response: Response<S>,
+    pub(crate) store_version: u128,
+    pub(crate) mutation: Option<Mutation<S>>,
+}
+
+impl<S> CatchUp<S>
+where
+    S: Shared,
+{
+    pub(crate) fn new(
+        request: &Header,
+        response: Response<S>,
+        store_version: u128,
+        mutation: Option<Mutation<S>>,
+    ) -> Self {
+        let len = mutation
+            .as_ref()
+            .map_or(0, |mutation| mutation.header().len);
+        Self {
+            header: Header::new(Kind::CatchUp, request.version, request.uuid, len),
+            response,
+            store_version,
+            mutation,
+        }
+    }
+
+    /// Returns the version the candidate is at once it applies the mutation.
+    pub fn store_version(&self) -> u128 {
+        self.store_version
+    }
+
+    pub fn mutation(&self) -> Option<&Mutation<S>> {
+        self.mutation.as_ref()
+    }
+
+    pub fn into_mutation(self) -> Option<Mutation<S>> {
+        self.mutation
+    }
+
+    /// Returns `true` if this ends the stream.
+    pub fn is_last(&self) -> bool {
+        self.mutation.is_none()
+    }
+}
+
+impl<R, O> PartialDecode<R, O> for CatchUp<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode(header: Header, reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        assert_eq!(header.kind, Kind::CatchUp);
+
+        let response = Response::decode_owned(reader, buffer)?;
+        let store_version = u128::decode(reader)?;
+        let mutation = Option::decode_owned(reader, buffer)?;
+
+        Ok(Self {
+            header,
+            response,
+            store_version,
+            mutation,
+        })
+    }
+}
+
+impl<W, S> Encode<W> for CatchUp<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.header.encode(writer)?;
+        self.response.encode(writer)?;
+        self.store_version.encode(writer)?;
+        self.mutation.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+impl<S> Ack<S> for CatchUp<S>
+where
+    S: Shared,
+{
+    fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn response(&self) -> Response<S> {
+        self.response.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use matches::assert_matches;
+
+    use crate::{
+        buffer::{binary_data, byte_str},
+        deque_codec::Enqueue,
+        full_decode,
+        kv_store_codec::{Delete, Get, Put},
+        tests::verify_encode_decode,
+        DecodeOwned, Encode, Error, Header, Kind, Packet, Pool, PoolImpl, Response, SharedImpl,
+    };
+
+    use super::{CatchUp, Mutation};
+
+    impl CatchUp<SharedImpl> {
+        pub fn new_test(store_version: u128, mutation: Option<Mutation<SharedImpl>>) -> Self {
+            Self::new(
+                &Header::new_test_ack(Kind::CatchUpRequest),
+                Response::success(),
+                store_version,
+                mutation,
+            )
+        }
+    }
+
+    #[test]
+    fn encode_decode() {
+        for mutation in [
+            Mutation::Put(Put::new(1, 2, binary_data(b"kitty"), binary_data(b"meow"))),
+            Mutation::Delete(Delete::new(1, 3, binary_data(b"kitty"))),
+            Mutation::Enqueue(Enqueue::new(
+                1,
+                4,
+                byte_str(b"/tmp/kitties"),
+                binary_data(b"purr"),
+            )),
+        ] {
+            verify_encode_decode(Packet::CatchUp(CatchUp::new_test(11, Some(mutation))));
+        }
+        verify_encode_decode(Packet::CatchUp(CatchUp::new_test(11, None)));
+    }
+
+    #[test]
+    fn decode_rejects_non_mutations() {
+        let mut bytes = vec![];
+        Get::<SharedImpl>::new(1, 2, binary_data(b"kitty"))
+            .encode(&mut bytes)
+            .expect("encode");
+
+        let pool = PoolImpl::new(64, 1);
+        let mut buffer = pool.acquire("catch up");
+        assert_matches!(
+            Mutation::decode_owned(&mut bytes.as_slice(), &mut buffer),
+            Err(Error::InvalidHeaderKind(_))
+        );
+    }
+
+    #[test]
+    fn decode_rejects_unknown_kind() {
+        let put = Put::new(1, 2, binary_data(b"kitty"), binary_data(b"meow"));
+        let mut nested = vec![];
+        put.encode(&mut nested).expect("encode");
+        let mut bytes = vec![];
+        CatchUp::new_test(11, Some(Mutation::Put(put)))
+            .encode(&mut bytes)
+            .expect("encode");
+
+        // the mutation is encoded last, starting with its kind.
+        let kind = bytes.len() - nested.len();
+        assert_eq!(bytes[kind..], nested);
+        bytes[kind] = 0xff;
+
+        let pool = PoolImpl::new(1024, 1);
+        let mut buffer = pool.acquire("catch up");
+        assert_matches!(
+            full_decode(&mut bytes.as_slice(), &mut buffer, None),
+            Err(Error::InvalidHeaderKind(0xff))
+        );
+    }
+}
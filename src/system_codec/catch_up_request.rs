@@ -0,0 +1,144 @@
+use std::{
+    io::{Read, Write},
+    marker::PhantomData,
+};
+
+use crate::{
+    buffer::{ByteStr, Owned, Shared},
+    header::{Uuid, Version},
+    Decode, Encode, Error, Header, Kind, PartialDecode, Response,
+};
+
+use super::{CatchUp, Mutation};
+
+/// Sent from a `Candidate` that already has most of the data to the `Tail`, asking for only the
+/// mutations after `store_version` instead of a full [`Transfer`](super::Transfer).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct CatchUpRequest<S>
+where
+    S: Shared,
+{
+    pub(crate) header: Header,
+    pub(crate) store_version: u128,
+    _phantom: PhantomData<S>,
+}
+
+impl<S> CatchUpRequest<S>
+where
+    S: Shared,
+{
+    /// `store_version` is the last version the candidate applied, as sent in its `Join`.
+    pub fn new(version: impl Into<Version>, uuid: impl Into<Uuid>, store_version: u128) -> Self {
+        Self {
+            header: Header::new(Kind::CatchUpRequest, version, uuid, 0),
+            store_version,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    pub fn store_version(&self) -> u128 {
+        self.store_version
+    }
+
+    /// Sends one missing mutation, which brings the candidate to `store_version`.
+    pub fn mutation(&self, store_version: u128, mutation: Mutation<S>) -> CatchUp<S> {
+        CatchUp::new(
+            &self.header,
+            Response::success(),
+            store_version,
+            Some(mutation),
+        )
+    }
+
+    /// Ends the stream once the candidate has every mutation up to `store_version`.
+    pub fn caught_up(self, store_version: u128) -> CatchUp<S> {
+        CatchUp::new(&self.header, Response::success(), store_version, None)
+    }
+
+    /// Ends the stream without catching up, for example with
+    /// [`LOG_TRUNCATED`](crate::LOG_TRUNCATED) when the tail no longer has the mutations and the
+    /// candidate needs a full transfer.
+    pub fn nack(self, response_code: u8, reason: Option<ByteStr<S>>) -> CatchUp<S> {
+        CatchUp::new(
+            &self.header,
+            Response::fail(response_code, reason),
+            self.store_version,
+            None,
+        )
+    }
+}
+
+impl<R, O> PartialDecode<R, O> for CatchUpRequest<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode(header: Header, reader: &mut R, _: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        assert_eq!(header.kind, Kind::CatchUpRequest);
+
+        let store_version = u128::decode(reader)?;
+
+        Ok(Self {
+            header,
+            store_version,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<W, S> Encode<W> for CatchUpRequest<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.header.encode(writer)?;
+        self.store_version.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        buffer::binary_data, kv_store_codec::Delete, system_codec::Mutation,
+        tests::verify_encode_decode, Ack, Packet, SharedImpl, LOG_TRUNCATED, SUCCESS,
+    };
+
+    use super::CatchUpRequest;
+
+    #[test]
+    fn acks() {
+        let request = CatchUpRequest::<SharedImpl>::new(1, 2, 10);
+
+        let catch_up = request.mutation(
+            11,
+            Mutation::Delete(Delete::new(1, 3, binary_data(b"kitty"))),
+        );
+        assert_eq!(catch_up.response().code(), SUCCESS);
+        assert_eq!(catch_up.store_version(), 11);
+        assert!(catch_up.mutation().is_some());
+
+        let done = request.clone().caught_up(11);
+        assert_eq!(done.response().code(), SUCCESS);
+        assert!(done.mutation().is_none());
+
+        let nack = request.nack(LOG_TRUNCATED, None);
+        assert_eq!(nack.response().code(), LOG_TRUNCATED);
+        assert_eq!(nack.store_version(), 10);
+    }
+
+    #[test]
+    fn encode_decode() {
+        verify_encode_decode(Packet::CatchUpRequest(CatchUpRequest::new(1, 2, 10)));
+    }
+}
@@ -3,11 +3,13 @@ use std::io::{Read, Write};
 use crate::{
     buffer::{ByteStr, Owned, Shared},
     header::{Uuid, Version},
-    Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response,
+    Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response, STALE_EPOCH,
 };
 
-use super::{JoinAck, Role};
+use super::{Address, JoinAck, NodeMetadata, Role};
 
+/// Sent from a node to the `Operator` to join the chain. `epoch` is the latest configuration the
+/// node has seen, so the operator can tell when a node is running an old one.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[repr(C)]
 pub struct Join<S>
@@ -18,6 +20,8 @@ where
     pub(crate) role: Role<S>,
     pub(crate) instance: u128,
     pub(crate) successor_lost: bool,
+    pub(crate) epoch: u64,
+    pub(crate) metadata: NodeMetadata<S>,
 }
 
 impl<S> Join<S>
@@ -30,15 +34,25 @@ where
         role: Role<S>,
         instance: u128,
         successor_lost: bool,
+        epoch: u64,
     ) -> Self {
         Self {
             header: Header::new(Kind::Join, version, uuid, role.encode_len()),
             role,
             instance,
             successor_lost,
+            epoch,
+            metadata: NodeMetadata::new(),
         }
     }
 
+    /// Sets what the node tells the operator about itself, for placing it in a chain.
+    pub fn with_metadata(mut self, metadata: NodeMetadata<S>) -> Self {
+        self.header.len = self.role.encode_len() + metadata.encode_len();
+        self.metadata = metadata;
+        self
+    }
+
     pub fn header(&self) -> Header {
         self.header
     }
@@ -55,7 +69,20 @@ where
         self.successor_lost
     }
 
-    pub fn addr(&self) -> Option<&ByteStr<S>> {
+    pub fn metadata(&self) -> &NodeMetadata<S> {
+        &self.metadata
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns `true` if the node is running an older configuration than `epoch`.
+    pub fn is_stale(&self, epoch: u64) -> bool {
+        self.epoch < epoch
+    }
+
+    pub fn addr(&self) -> Option<&Address<S>> {
         match &self.role {
             Role::Backend(addr) => Some(addr),
             Role::Frontend(addr) => Some(addr),
@@ -63,10 +90,12 @@ where
         }
     }
 
-    pub fn ack(self) -> JoinAck<S> {
+    /// Acks the join with the operator's current `epoch`.
+    pub fn ack(self, epoch: u64) -> JoinAck<S> {
         JoinAck {
             header: Header::new(Kind::JoinAck, self.header.version, self.header.uuid, 0),
             response: Response::success(),
+            epoch,
         }
     }
 
@@ -74,6 +103,17 @@ where
         JoinAck {
             header: Header::new(Kind::JoinAck, self.header.version, self.header.uuid, 0),
             response: Response::fail(response_code, reason),
+            epoch: self.epoch,
+        }
+    }
+
+    /// Nacks the join with [`STALE_EPOCH`] because the node is running an older configuration than
+    /// the operator's current `epoch`.
+    pub fn stale(self, epoch: u64) -> JoinAck<S> {
+        JoinAck {
+            header: Header::new(Kind::JoinAck, self.header.version, self.header.uuid, 0),
+            response: Response::fail(STALE_EPOCH, None),
+            epoch,
         }
     }
 }
@@ -92,12 +132,16 @@ where
         let role = Role::decode_owned(reader, buffer)?;
         let version = u128::decode(reader)?;
         let successor_lost = u8::decode(reader)? > 0;
+        let epoch = u64::decode(reader)?;
+        let metadata = NodeMetadata::decode_owned(reader, buffer)?;
 
         Ok(Self {
             header,
             role,
             instance: version,
             successor_lost,
+            epoch,
+            metadata,
         })
     }
 }
@@ -112,6 +156,8 @@ where
         self.role.encode(writer)?;
         self.instance.encode(writer)?;
         u8::from(self.successor_lost).encode(writer)?;
+        self.epoch.encode(writer)?;
+        self.metadata.encode(writer)?;
 
         Ok(())
     }
@@ -120,21 +166,36 @@ where
 #[cfg(test)]
 mod test {
     use crate::{
-        buffer::byte_str, system_codec::Role, tests::verify_encode_decode, Ack, Packet,
-        INTERNAL_ERROR, SUCCESS,
+        buffer::byte_str,
+        system_codec::{test_address, NodeMetadata, Role},
+        tests::verify_encode_decode,
+        Ack, Packet, INTERNAL_ERROR, STALE_EPOCH, SUCCESS,
     };
 
     use super::Join;
 
     #[test]
     fn acks() {
-        let join = Join::new(1, 2, Role::Backend(byte_str(b"localhost")), 1, false);
+        let join = Join::new(1, 2, Role::Backend(test_address(b"localhost")), 1, false, 3);
 
-        let ack = join.clone().ack();
+        let ack = join.clone().ack(3);
         assert_eq!(ack.response().code(), SUCCESS);
+        assert_eq!(ack.epoch(), 3);
 
         let nack = join.nack(INTERNAL_ERROR, None);
         assert_eq!(nack.response().code(), INTERNAL_ERROR);
+        assert_eq!(nack.epoch(), 3);
+    }
+
+    #[test]
+    fn stale() {
+        let join = Join::new(1, 2, Role::Backend(test_address(b"localhost")), 1, false, 3);
+        assert!(!join.is_stale(3));
+        assert!(join.is_stale(4));
+
+        let stale = join.stale(4);
+        assert_eq!(stale.response().code(), STALE_EPOCH);
+        assert_eq!(stale.epoch(), 4);
     }
 
     #[test]
@@ -142,9 +203,18 @@ mod test {
         verify_encode_decode(Packet::Join(Join::new(
             1,
             1,
-            Role::Backend(byte_str(b"localhost")),
+            Role::Backend(test_address(b"localhost")),
             1,
             false,
+            3,
         )));
+        verify_encode_decode(Packet::Join(
+            Join::new(1, 1, Role::Backend(test_address(b"localhost")), 1, false, 3).with_metadata(
+                NodeMetadata::new()
+                    .with_zone(byte_str(b"us-east-1a"))
+                    .with_memory(64 << 30)
+                    .with_label(byte_str(b"rack"), byte_str(b"r12")),
+            ),
+        ));
     }
 }
@@ -1,7 +1,8 @@
 use std::io::{Read, Write};
 
 use crate::{
-    buffer::Owned, Ack, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response, Shared,
+    buffer::Owned, Ack, Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response,
+    Shared,
 };
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -12,6 +13,17 @@ where
 {
     pub(crate) header: Header,
     pub(crate) response: Response<S>,
+    pub(crate) epoch: u64,
+}
+
+impl<S> JoinAck<S>
+where
+    S: Shared,
+{
+    /// Returns the operator's current configuration epoch.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
 }
 
 impl<R, O> PartialDecode<R, O> for JoinAck<O::Shared>
@@ -26,8 +38,13 @@ where
         assert_eq!(header.kind, Kind::JoinAck);
 
         let response = Response::decode_owned(reader, buffer)?;
+        let epoch = u64::decode(reader)?;
 
-        Ok(Self { header, response })
+        Ok(Self {
+            header,
+            response,
+            epoch,
+        })
     }
 }
 
@@ -39,6 +56,7 @@ where
     fn encode(&self, writer: &mut W) -> Result<(), Error> {
         self.header.encode(writer)?;
         self.response.encode(writer)?;
+        self.epoch.encode(writer)?;
 
         Ok(())
     }
@@ -64,16 +82,17 @@ mod test {
     use super::JoinAck;
 
     impl JoinAck<SharedImpl> {
-        pub fn new(response: Response<SharedImpl>, uuid: u128) -> Self {
+        pub fn new(response: Response<SharedImpl>, uuid: u128, epoch: u64) -> Self {
             Self {
                 header: Header::new_test_full(Kind::JoinAck, 0, uuid),
                 response,
+                epoch,
             }
         }
     }
 
     #[test]
     fn encode_decode() {
-        verify_encode_decode(Packet::JoinAck(JoinAck::new(Response::success(), 1)));
+        verify_encode_decode(Packet::JoinAck(JoinAck::new(Response::success(), 1, 3)));
     }
 }
@@ -0,0 +1,150 @@
+use std::io::{Read, Write};
+
+use crate::{
+    buffer::{ByteStr, Owned, Shared},
+    header::{Uuid, Version},
+    Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response,
+};
+
+use super::{Address, LeaveAck, Role};
+
+/// Sent from a node to the `Operator` when it wants to leave the chain, for example for a planned
+/// restart. The node keeps serving and sends a `Leave` again once `in_flight` reaches zero, only
+/// then does the operator send `Report`s that bypass it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct Leave<S>
+where
+    S: Shared,
+{
+    pub(crate) header: Header,
+    pub(crate) role: Role<S>,
+    pub(crate) in_flight: u64,
+}
+
+impl<S> Leave<S>
+where
+    S: Shared,
+{
+    pub fn new(
+        version: impl Into<Version>,
+        uuid: impl Into<Uuid>,
+        role: Role<S>,
+        in_flight: u64,
+    ) -> Self {
+        Self {
+            header: Header::new(Kind::Leave, version, uuid, role.encode_len()),
+            role,
+            in_flight,
+        }
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    pub fn role(&self) -> &Role<S> {
+        &self.role
+    }
+
+    pub fn addr(&self) -> Option<&Address<S>> {
+        match &self.role {
+            Role::Backend(addr) => Some(addr),
+            Role::Frontend(addr) => Some(addr),
+            Role::Observer => None,
+        }
+    }
+
+    /// Returns the number of writes the node has forwarded that are not yet acked.
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight
+    }
+
+    /// Returns `true` if the node has no writes in flight and can be bypassed.
+    pub fn is_drained(&self) -> bool {
+        self.in_flight == 0
+    }
+
+    pub fn ack(self) -> LeaveAck<S> {
+        LeaveAck {
+            header: Header::new(Kind::LeaveAck, self.header.version, self.header.uuid, 0),
+            response: Response::success(),
+        }
+    }
+
+    pub fn nack(self, response_code: u8, reason: Option<ByteStr<S>>) -> LeaveAck<S> {
+        LeaveAck {
+            header: Header::new(Kind::LeaveAck, self.header.version, self.header.uuid, 0),
+            response: Response::fail(response_code, reason),
+        }
+    }
+}
+
+impl<R, O> PartialDecode<R, O> for Leave<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode(header: Header, reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        assert_eq!(header.kind, Kind::Leave);
+
+        let role = Role::decode_owned(reader, buffer)?;
+        let in_flight = u64::decode(reader)?;
+
+        Ok(Self {
+            header,
+            role,
+            in_flight,
+        })
+    }
+}
+
+impl<W, S> Encode<W> for Leave<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.header.encode(writer)?;
+        self.role.encode(writer)?;
+        self.in_flight.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        system_codec::{test_address, Role},
+        tests::verify_encode_decode,
+        Ack, Packet, CHAIN_NOT_READY, SUCCESS,
+    };
+
+    use super::Leave;
+
+    #[test]
+    fn acks() {
+        let leave = Leave::new(1, 2, Role::Backend(test_address(b"localhost")), 3);
+        assert!(!leave.is_drained());
+
+        let ack = leave.clone().ack();
+        assert_eq!(ack.response().code(), SUCCESS);
+
+        let nack = leave.nack(CHAIN_NOT_READY, None);
+        assert_eq!(nack.response().code(), CHAIN_NOT_READY);
+    }
+
+    #[test]
+    fn encode_decode() {
+        verify_encode_decode(Packet::Leave(Leave::new(
+            1,
+            1,
+            Role::Backend(test_address(b"localhost")),
+            0,
+        )));
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    io::{self, Read, Write},
+    mem::size_of,
+};
+
+use crate::{
+    buffer::{BinaryData, ByteStr, Owned, Shared},
+    Decode, DecodeOwned, Encode, Error,
+};
+
+const ZONE: u8 = 1;
+const FREE_DISK: u8 = 2;
+const MEMORY: u8 = 3;
+const LABEL: u8 = 4;
+
+/// What a node tells the operator about itself when it joins, so the operator can place it in a
+/// chain, for example keeping replicas in different zones.
+///
+/// Every field is encoded as a tag followed by a length prefixed value. Fields with tags this
+/// version doesn't know are kept as `extensions` and encoded again as they were, so new fields can
+/// be added without breaking older nodes or operators.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NodeMetadata<S>
+where
+    S: Shared,
+{
+    zone: Option<ByteStr<S>>,
+    free_disk: Option<u64>,
+    memory: Option<u64>,
+    labels: Vec<(ByteStr<S>, ByteStr<S>)>,
+    extensions: Vec<(u8, BinaryData<S>)>,
+}
+
+impl<S> NodeMetadata<S>
+where
+    S: Shared,
+{
+    pub fn new() -> Self {
+        Self {
+            zone: None,
+            free_disk: None,
+            memory: None,
+            labels: vec![],
+            extensions: vec![],
+        }
+    }
+
+    /// Sets the availability zone of the node.
+    pub fn with_zone(mut self, zone: ByteStr<S>) -> Self {
+        self.zone = Some(zone);
+        self
+    }
+
+    /// Sets the free disk of the node in bytes.
+    pub fn with_free_disk(mut self, bytes: u64) -> Self {
+        self.free_disk = Some(bytes);
+        self
+    }
+
+    /// Sets the memory of the node in bytes.
+    pub fn with_memory(mut self, bytes: u64) -> Self {
+        self.memory = Some(bytes);
+        self
+    }
+
+    pub fn with_label(mut self, key: ByteStr<S>, value: ByteStr<S>) -> Self {
+        self.labels.push((key, value));
+        self
+    }
+
+    pub fn zone(&self) -> Option<&ByteStr<S>> {
+        self.zone.as_ref()
+    }
+
+    pub fn free_disk(&self) -> Option<u64> {
+        self.free_disk
+    }
+
+    pub fn memory(&self) -> Option<u64> {
+        self.memory
+    }
+
+    pub fn labels(&self) -> &[(ByteStr<S>, ByteStr<S>)] {
+        &self.labels
+    }
+
+    /// Returns the value of the first label with `key`.
+    pub fn label(&self, key: &[u8]) -> Option<&ByteStr<S>> {
+        self.labels
+            .iter()
+            .find(|(k, _)| k.as_slice() == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Returns the fields this version doesn't know, by tag.
+    pub fn extensions(&self) -> &[(u8, BinaryData<S>)] {
+        &self.extensions
+    }
+
+    pub fn encode_len(&self) -> usize {
+        self.zone.as_ref().map(|z| z.len()).unwrap_or(0)
+            + self
+                .labels
+                .iter()
+                .map(|(k, v)| k.len() + v.len())
+                .sum::<usize>()
+            + self
+                .extensions
+                .iter()
+                .map(|(_, data)| data.len())
+                .sum::<usize>()
+    }
+
+    fn fields(&self) -> usize {
+        usize::from(self.zone.is_some())
+            + usize::from(self.free_disk.is_some())
+            + usize::from(self.memory.is_some())
+            + self.labels.len()
+            + self.extensions.len()
+    }
+}
+
+impl<S> Default for NodeMetadata<S>
+where
+    S: Shared,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W, S> Encode<W> for NodeMetadata<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.fields().encode(writer)?;
+        if let Some(zone) = &self.zone {
+            ZONE.encode(writer)?;
+            (size_of::<usize>() + zone.len()).encode(writer)?;
+            zone.encode(writer)?;
+        }
+        for (tag, value) in [(FREE_DISK, self.free_disk), (MEMORY, self.memory)] {
+            if let Some(value) = value {
+                tag.encode(writer)?;
+                size_of::<u64>().encode(writer)?;
+                value.encode(writer)?;
+            }
+        }
+        for (key, value) in &self.labels {
+            LABEL.encode(writer)?;
+            (2 * size_of::<usize>() + key.len() + value.len()).encode(writer)?;
+            key.encode(writer)?;
+            value.encode(writer)?;
+        }
+        for (tag, data) in &self.extensions {
+            tag.encode(writer)?;
+            data.encode(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R, O> DecodeOwned<R, O> for NodeMetadata<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode_owned(reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let mut metadata = Self::new();
+        let fields = usize::decode(reader)?;
+        for _ in 0..fields {
+            let tag = u8::decode(reader)?;
+            if !matches!(tag, ZONE | FREE_DISK | MEMORY | LABEL) {
+                let data = BinaryData::decode_owned(reader, buffer)?;
+                metadata.extensions.push((tag, data));
+                continue;
+            }
+
+            // a known field may gain more data later, which is skipped.
+            let len = usize::decode(reader)?;
+            let mut field = reader.take(len as u64);
+            match tag {
+                ZONE => metadata.zone = Some(ByteStr::decode_owned(&mut field, buffer)?),
+                FREE_DISK => metadata.free_disk = Some(u64::decode(&mut field)?),
+                MEMORY => metadata.memory = Some(u64::decode(&mut field)?),
+                _ => {
+                    let key = ByteStr::decode_owned(&mut field, buffer)?;
+                    let value = ByteStr::decode_owned(&mut field, buffer)?;
+                    metadata.labels.push((key, value));
+                }
+            }
+            io::copy(&mut field, &mut io::sink()).map_err(Error::Decode)?;
+        }
+
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        buffer::{binary_data, byte_str, Pool, PoolImpl},
+        DecodeOwned, Encode, SharedImpl,
+    };
+
+    use super::NodeMetadata;
+
+    fn decode(bytes: &[u8]) -> NodeMetadata<SharedImpl> {
+        let pool = PoolImpl::new(1024, 1);
+        let mut buffer = pool.acquire("decode metadata");
+        NodeMetadata::decode_owned(&mut &bytes[..], &mut buffer).unwrap()
+    }
+
+    #[test]
+    fn encode_decode() {
+        for metadata in [
+            NodeMetadata::new(),
+            NodeMetadata::new()
+                .with_zone(byte_str(b"us-east-1a"))
+                .with_free_disk(1 << 40)
+                .with_memory(64 << 30)
+                .with_label(byte_str(b"rack"), byte_str(b"r12"))
+                .with_label(byte_str(b"tier"), byte_str(b"ssd")),
+        ] {
+            let mut bytes = Vec::new();
+            metadata.encode(&mut bytes).unwrap();
+            let decoded = decode(&bytes);
+            assert_eq!(decoded, metadata);
+            assert_eq!(decoded.encode_len(), metadata.encode_len());
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let metadata = NodeMetadata::new()
+            .with_label(byte_str(b"rack"), byte_str(b"r12"))
+            .with_label(byte_str(b"rack"), byte_str(b"r13"));
+        assert_eq!(metadata.label(b"rack"), Some(&byte_str(b"r12")));
+        assert_eq!(metadata.label(b"tier"), None);
+        assert_eq!(metadata.zone(), None);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        // a memory field with extra data after it, then a field from a newer version.
+        let mut bytes = Vec::new();
+        2usize.encode(&mut bytes).unwrap();
+        3u8.encode(&mut bytes).unwrap();
+        12usize.encode(&mut bytes).unwrap();
+        1024u64.encode(&mut bytes).unwrap();
+        7u32.encode(&mut bytes).unwrap();
+        99u8.encode(&mut bytes).unwrap();
+        binary_data(b"gpu").encode(&mut bytes).unwrap();
+
+        let metadata = decode(&bytes);
+        assert_eq!(metadata.memory(), Some(1024));
+        assert_eq!(metadata.extensions(), &[(99, binary_data(b"gpu"))]);
+
+        let mut forwarded = Vec::new();
+        metadata.encode(&mut forwarded).unwrap();
+        assert_eq!(decode(&forwarded), metadata);
+    }
+}
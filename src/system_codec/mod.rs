@@ -15,20 +15,57 @@ pub use join::Join;
 mod join_ack;
 pub use join_ack::JoinAck;
 
+mod metadata;
+pub use metadata::NodeMetadata;
+
+mod leave;
+pub use leave::Leave;
+
+mod leave_ack;
+pub use leave_ack::LeaveAck;
+
+mod address;
+pub use address::Address;
+
+mod shard;
+pub(crate) use shard::slot_of;
+pub use shard::{slot, Route, Shard, SlotRange, SLOTS};
+
 mod transfer;
-pub use transfer::Transfer;
+pub use transfer::{StreamedTransfer, Transfer};
 
 mod transfer_ack;
 pub use transfer_ack::TransferAck;
 
+mod transfer_begin;
+pub use transfer_begin::{ManifestEntry, TransferBegin};
+
+mod transfer_begin_ack;
+pub use transfer_begin_ack::TransferBeginAck;
+
+mod transfer_end;
+pub use transfer_end::TransferEnd;
+
+mod transfer_end_ack;
+pub use transfer_end_ack::TransferEndAck;
+
+mod transfer_progress;
+pub use transfer_progress::{StallTimer, TransferProgress};
+
+mod catch_up_request;
+pub use catch_up_request::CatchUpRequest;
+
+mod catch_up;
+pub use catch_up::{CatchUp, Mutation};
+
 mod ping;
 pub use ping::Ping;
 
 mod ping_ack;
-pub use ping_ack::PingAck;
+pub use ping_ack::{NodeStats, PingAck};
 
 use crate::{
-    buffer::{ByteStr, Owned, Shared},
+    buffer::{Owned, Shared},
     Decode, DecodeOwned, Encode,
 };
 
@@ -50,8 +87,26 @@ pub const TRANSFER_ACK: u8 = START + 5;
 pub const PING: u8 = START + 6;
 /// Ack for a ping
 pub const PING_ACK: u8 = START + 7;
-
-pub const END: u8 = START + 7;
+/// A message to start a transfer session with a manifest of the files it will send.
+pub const TRANSFER_BEGIN: u8 = START + 8;
+/// An ack for a transfer begin message.
+pub const TRANSFER_BEGIN_ACK: u8 = START + 9;
+/// A message to end a transfer session.
+pub const TRANSFER_END: u8 = START + 10;
+/// An ack confirming that every file in the manifest arrived intact.
+pub const TRANSFER_END_ACK: u8 = START + 11;
+/// A message from the tail to the operator reporting how far a transfer has got.
+pub const TRANSFER_PROGRESS: u8 = START + 12;
+/// A message from a candidate asking for the mutations it is missing.
+pub const CATCH_UP_REQUEST: u8 = START + 13;
+/// A missing mutation, streamed in reply to a catch up request.
+pub const CATCH_UP: u8 = START + 14;
+/// A message from a node to the operator that it is draining and wants to leave the chain.
+pub const LEAVE: u8 = START + 15;
+/// An ack for a leave message.
+pub const LEAVE_ACK: u8 = START + 16;
+
+pub const END: u8 = START + 16;
 
 pub fn is_system_message(kind: u8) -> bool {
     (START..=END).contains(&kind)
@@ -63,8 +118,8 @@ pub enum Role<S>
 where
     S: Shared,
 {
-    Backend(ByteStr<S>),  // 1
-    Frontend(ByteStr<S>), // 2
+    Backend(Address<S>),  // 1
+    Frontend(Address<S>), // 2
     Observer,             // 3
 }
 
@@ -74,7 +129,7 @@ where
 {
     pub fn encode_len(&self) -> usize {
         match self {
-            Role::Backend(addr) | Role::Frontend(addr) => addr.len(),
+            Role::Backend(addr) | Role::Frontend(addr) => addr.encode_len(),
             Role::Observer => 0,
         }
     }
@@ -115,8 +170,8 @@ where
     {
         let kind = u8::decode(reader)?;
         match kind {
-            1 => Ok(Role::Backend(ByteStr::decode_owned(reader, buffer)?)),
-            2 => Ok(Role::Frontend(ByteStr::decode_owned(reader, buffer)?)),
+            1 => Ok(Role::Backend(Address::decode_owned(reader, buffer)?)),
+            2 => Ok(Role::Frontend(Address::decode_owned(reader, buffer)?)),
             3 => Ok(Role::Observer),
             _ => Err(crate::Error::SystemBadRole(kind)),
         }
@@ -130,20 +185,19 @@ where
 {
     // Backends
     Head {
-        next: ByteStr<S>,
+        next: Address<S>,
     }, // 1
     Middle {
-        next: ByteStr<S>,
+        next: Address<S>,
     }, // 2
     Tail {
-        candidate: Option<ByteStr<S>>,
+        candidate: Option<Address<S>>,
     }, // 3
     Candidate, // 4
 
     // Frontends
     Frontend {
-        head: Option<ByteStr<S>>,
-        tail: Option<ByteStr<S>>,
+        routes: Vec<Route<S>>,
     }, // 5
 
     // Observer
@@ -158,18 +212,23 @@ where
 {
     pub fn encode_len(&self) -> usize {
         match self {
-            Position::Head { next } | Position::Middle { next } => next.len(),
-            Position::Tail { candidate } => candidate.as_ref().map(|c| c.len()).unwrap_or(0),
+            Position::Head { next } | Position::Middle { next } => next.encode_len(),
+            Position::Tail { candidate } => candidate.as_ref().map(|c| c.encode_len()).unwrap_or(0),
             Position::Candidate => 0,
-            Position::Frontend { head, tail } => {
-                head.as_ref().map(|h| h.len()).unwrap_or(0)
-                    + tail.as_ref().map(|t| t.len()).unwrap_or(0)
-            }
+            Position::Frontend { routes } => routes.iter().map(Route::encode_len).sum(),
             Position::Observer { chain } => {
                 chain.iter().map(|role| role.encode_len()).sum::<usize>() + size_of::<u64>()
             }
         }
     }
+
+    /// Returns the route a frontend uses for `slot`, see [`slot`].
+    pub fn route(&self, slot: u16) -> Option<&Route<S>> {
+        match self {
+            Position::Frontend { routes } => routes.iter().find(|route| route.shard.owns(slot)),
+            _ => None,
+        }
+    }
 }
 
 impl<W, S> Encode<W> for Position<S>
@@ -197,10 +256,9 @@ where
             }
 
             // Frontends
-            Position::Frontend { head, tail } => {
+            Position::Frontend { routes } => {
                 5u8.encode(writer)?;
-                head.encode(writer)?;
-                tail.encode(writer)?;
+                routes.encode(writer)?;
             }
 
             // Observer
@@ -227,11 +285,11 @@ where
         match kind {
             // Backends
             1 => {
-                let next = ByteStr::decode_owned(reader, buffer)?;
+                let next = Address::decode_owned(reader, buffer)?;
                 Ok(Position::Head { next })
             }
             2 => {
-                let next = ByteStr::decode_owned(reader, buffer)?;
+                let next = Address::decode_owned(reader, buffer)?;
                 Ok(Position::Middle { next })
             }
             3 => {
@@ -242,9 +300,8 @@ where
 
             // Frontends
             5 => {
-                let head = Option::decode_owned(reader, buffer)?;
-                let tail = Option::decode_owned(reader, buffer)?;
-                Ok(Position::Frontend { head, tail })
+                let routes = Vec::decode_owned(reader, buffer)?;
+                Ok(Position::Frontend { routes })
             }
 
             // Observer
@@ -259,16 +316,24 @@ where
     }
 }
 
+#[cfg(test)]
+pub(crate) fn test_address(host: &[u8]) -> Address<crate::buffer::SharedImpl> {
+    Address::Dns {
+        host: crate::buffer::byte_str(host),
+        port: 8080,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use matches::assert_matches;
 
     use crate::{
-        buffer::{byte_str, Pool, PoolImpl},
+        buffer::{Pool, PoolImpl},
         DecodeOwned, Encode,
     };
 
-    use super::{Position, Role, END, START};
+    use super::{test_address, Position, Role, Route, Shard, SlotRange, END, START};
 
     #[test]
     fn is_system_message() {
@@ -281,8 +346,8 @@ mod test {
     #[test]
     fn encode_decode_role() {
         for role in &[
-            Role::Backend(byte_str(b"backend")),
-            Role::Frontend(byte_str(b"frontend")),
+            Role::Backend(test_address(b"backend")),
+            Role::Frontend(test_address(b"frontend")),
             Role::Observer,
         ] {
             let mut bytes = Vec::new();
@@ -308,23 +373,29 @@ mod test {
     fn encode_decode_position() {
         for position in &[
             Position::Head {
-                next: byte_str(b"next"),
+                next: test_address(b"next"),
             },
             Position::Middle {
-                next: byte_str(b"next"),
+                next: test_address(b"next"),
             },
             Position::Tail {
-                candidate: Some(byte_str(b"candidate")),
+                candidate: Some(test_address(b"candidate")),
             },
             Position::Candidate,
             Position::Frontend {
-                head: Some(byte_str(b"head")),
-                tail: Some(byte_str(b"tail")),
+                routes: vec![
+                    Route::new(
+                        Shard::new(0, SlotRange::new(0, 8192)),
+                        Some(test_address(b"head")),
+                        Some(test_address(b"tail")),
+                    ),
+                    Route::new(Shard::new(1, SlotRange::new(8192, 16384)), None, None),
+                ],
             },
             Position::Observer {
                 chain: vec![
-                    Role::Backend(byte_str(b"backend")),
-                    Role::Frontend(byte_str(b"frontend")),
+                    Role::Backend(test_address(b"backend")),
+                    Role::Frontend(test_address(b"frontend")),
                     Role::Observer,
                 ],
             },
@@ -347,4 +418,25 @@ mod test {
             Err(crate::Error::SystemBadPosition(0))
         );
     }
+
+    #[test]
+    fn route() {
+        let position = Position::Frontend {
+            routes: vec![
+                Route::new(
+                    Shard::new(0, SlotRange::new(0, 8192)),
+                    Some(test_address(b"head-0")),
+                    Some(test_address(b"tail-0")),
+                ),
+                Route::new(
+                    Shard::new(1, SlotRange::new(8192, 16384)),
+                    Some(test_address(b"head-1")),
+                    Some(test_address(b"tail-1")),
+                ),
+            ],
+        };
+        assert_eq!(position.route(0).map(|route| route.shard.id), Some(0));
+        assert_eq!(position.route(8192).map(|route| route.shard.id), Some(1));
+        assert!(Position::<crate::SharedImpl>::Candidate.route(0).is_none());
+    }
 }
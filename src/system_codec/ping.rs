@@ -1,16 +1,19 @@
 use std::{
     io::{Read, Write},
     marker::PhantomData,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
     buffer::Owned,
     header::{Uuid, Version},
-    Encode, Error, Header, Kind, PartialDecode, Shared,
+    Decode, Encode, Error, Header, Kind, PartialDecode, Shared,
 };
 
-use super::PingAck;
+use super::{NodeStats, PingAck};
 
+/// Sent from the `Operator` to check a node is alive. It carries the time it was sent, which the
+/// `PingAck` echoes back so the operator can measure the round trip with its own clock.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[repr(C)]
 pub struct Ping<S>
@@ -18,6 +21,8 @@ where
     S: Shared,
 {
     pub(crate) header: Header,
+    /// Microseconds since the unix epoch.
+    pub(crate) sent_at: u64,
     _phantom: PhantomData<S>,
 }
 
@@ -25,9 +30,15 @@ impl<S> Ping<S>
 where
     S: Shared,
 {
+    /// Creates a ping sent now.
     pub fn new(version: impl Into<Version>, uuid: impl Into<Uuid>) -> Self {
+        Self::sent_at(version, uuid, SystemTime::now())
+    }
+
+    pub fn sent_at(version: impl Into<Version>, uuid: impl Into<Uuid>, at: SystemTime) -> Self {
         Self {
             header: Header::new(Kind::Ping, version, uuid, 0),
+            sent_at: to_micros(at),
             _phantom: PhantomData,
         }
     }
@@ -36,27 +47,47 @@ where
         self.header
     }
 
-    pub fn ack(self) -> PingAck<S> {
+    pub fn timestamp(&self) -> SystemTime {
+        from_micros(self.sent_at)
+    }
+
+    /// Acks the ping with the node's current `stats`.
+    pub fn ack(self, stats: NodeStats) -> PingAck<S> {
         PingAck {
             header: Header::new(Kind::PingAck, self.header.version, self.header.uuid, 0),
+            sent_at: self.sent_at,
+            stats,
             _phantom: PhantomData,
         }
     }
 }
 
+pub(crate) fn to_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| u64::try_from(since.as_micros()).unwrap_or(u64::MAX))
+        .unwrap_or(0)
+}
+
+pub(crate) fn from_micros(micros: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_micros(micros)
+}
+
 impl<R, O> PartialDecode<R, O> for Ping<O::Shared>
 where
     R: Read,
     O: Owned,
 {
-    fn decode(header: Header, _reader: &mut R, _: &mut O) -> Result<Self, Error>
+    fn decode(header: Header, reader: &mut R, _: &mut O) -> Result<Self, Error>
     where
         Self: Sized,
     {
         assert_eq!(header.kind, Kind::Ping);
 
+        let sent_at = u64::decode(reader)?;
+
         Ok(Self {
             header,
+            sent_at,
             _phantom: PhantomData,
         })
     }
@@ -69,6 +100,7 @@ where
 {
     fn encode(&self, writer: &mut W) -> Result<(), Error> {
         self.header.encode(writer)?;
+        self.sent_at.encode(writer)?;
 
         Ok(())
     }
@@ -76,16 +108,27 @@ where
 
 #[cfg(test)]
 mod test {
-    use crate::{tests::verify_encode_decode, Ack, Packet, SharedImpl, SUCCESS};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::{
+        system_codec::NodeStats, tests::verify_encode_decode, Ack, Packet, SharedImpl, SUCCESS,
+    };
 
     use super::Ping;
 
     #[test]
     fn test_ack() {
-        let ping = Ping::<SharedImpl>::new(1, 2);
-        let ping_ack = ping.ack();
+        let sent = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let ping = Ping::<SharedImpl>::sent_at(1, 2, sent);
+        assert_eq!(ping.timestamp(), sent);
 
+        let ping_ack = ping.ack(NodeStats::default());
         assert_eq!(ping_ack.response().code(), SUCCESS);
+        assert_eq!(ping_ack.timestamp(), sent);
+        assert_eq!(
+            ping_ack.rtt(sent + Duration::from_millis(3)),
+            Duration::from_millis(3)
+        );
     }
 
     #[test]
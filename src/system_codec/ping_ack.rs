@@ -1,9 +1,99 @@
 use std::{
     io::{Read, Write},
     marker::PhantomData,
+    time::{Duration, SystemTime},
 };
 
-use crate::{buffer::Owned, Ack, Encode, Error, Header, Kind, PartialDecode, Response, Shared};
+use crate::{
+    buffer::Owned, Ack, Decode, Encode, Error, Header, Kind, PartialDecode, PoolStats, Response,
+    Shared,
+};
+
+use super::ping::from_micros;
+
+/// How loaded a node is, sent back with every `PingAck` so the operator can tell a degraded node
+/// from a healthy one before it stops answering.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NodeStats {
+    /// The number of queues the node stores.
+    pub queues: u64,
+    /// The number of keys the node stores.
+    pub keys: u64,
+    /// The bytes of disk the node uses.
+    pub disk_usage: u64,
+    /// The most disk the node may use, the sum of `max_disk_usage` over its queues and store.
+    pub max_disk_usage: u64,
+    /// The number of pool blocks in use.
+    pub pool_in_use: u64,
+    /// The number of blocks in the pool.
+    pub pool_capacity: u64,
+    /// The number of requests the node is working on.
+    pub in_flight: u64,
+}
+
+impl NodeStats {
+    /// Fills in the pool fields from a snapshot of the node's pool.
+    pub fn with_pool(mut self, stats: &PoolStats) -> Self {
+        self.pool_in_use = stats.in_use as u64;
+        self.pool_capacity = stats.capacity as u64;
+        self
+    }
+
+    /// Returns the fraction of `max_disk_usage` in use, between `0.0` and `1.0`.
+    pub fn disk_utilization(&self) -> f64 {
+        if self.max_disk_usage == 0 {
+            return 0.0;
+        }
+        self.disk_usage as f64 / self.max_disk_usage as f64
+    }
+
+    /// Returns the fraction of the pool in use, between `0.0` and `1.0`.
+    pub fn pool_utilization(&self) -> f64 {
+        if self.pool_capacity == 0 {
+            return 0.0;
+        }
+        self.pool_in_use as f64 / self.pool_capacity as f64
+    }
+
+    /// Returns `true` if the disk or the pool is at least `threshold` full.
+    pub fn is_degraded(&self, threshold: f64) -> bool {
+        self.disk_utilization() >= threshold || self.pool_utilization() >= threshold
+    }
+}
+
+impl<R> Decode<R> for NodeStats
+where
+    R: Read,
+{
+    fn decode(reader: &mut R) -> Result<Self, Error> {
+        Ok(Self {
+            queues: u64::decode(reader)?,
+            keys: u64::decode(reader)?,
+            disk_usage: u64::decode(reader)?,
+            max_disk_usage: u64::decode(reader)?,
+            pool_in_use: u64::decode(reader)?,
+            pool_capacity: u64::decode(reader)?,
+            in_flight: u64::decode(reader)?,
+        })
+    }
+}
+
+impl<W> Encode<W> for NodeStats
+where
+    W: Write,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.queues.encode(writer)?;
+        self.keys.encode(writer)?;
+        self.disk_usage.encode(writer)?;
+        self.max_disk_usage.encode(writer)?;
+        self.pool_in_use.encode(writer)?;
+        self.pool_capacity.encode(writer)?;
+        self.in_flight.encode(writer)?;
+
+        Ok(())
+    }
+}
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[repr(C)]
@@ -12,22 +102,51 @@ where
     S: Shared,
 {
     pub(crate) header: Header,
+    /// The timestamp of the `Ping`, in microseconds since the unix epoch.
+    pub(crate) sent_at: u64,
+    pub(crate) stats: NodeStats,
     pub(crate) _phantom: PhantomData<S>,
 }
 
+impl<S> PingAck<S>
+where
+    S: Shared,
+{
+    /// Returns the time the `Ping` was sent.
+    pub fn timestamp(&self) -> SystemTime {
+        from_micros(self.sent_at)
+    }
+
+    /// Returns the round trip time of the ping, given the time the ack was received.
+    pub fn rtt(&self, received: SystemTime) -> Duration {
+        received
+            .duration_since(self.timestamp())
+            .unwrap_or(Duration::ZERO)
+    }
+
+    pub fn stats(&self) -> &NodeStats {
+        &self.stats
+    }
+}
+
 impl<R, O> PartialDecode<R, O> for PingAck<O::Shared>
 where
     R: Read,
     O: Owned,
 {
-    fn decode(header: Header, _reader: &mut R, _: &mut O) -> Result<Self, Error>
+    fn decode(header: Header, reader: &mut R, _: &mut O) -> Result<Self, Error>
     where
         Self: Sized,
     {
         assert_eq!(header.kind, Kind::PingAck);
 
+        let sent_at = u64::decode(reader)?;
+        let stats = NodeStats::decode(reader)?;
+
         Ok(Self {
             header,
+            sent_at,
+            stats,
             _phantom: PhantomData,
         })
     }
@@ -40,6 +159,8 @@ where
 {
     fn encode(&self, writer: &mut W) -> Result<(), Error> {
         self.header.encode(writer)?;
+        self.sent_at.encode(writer)?;
+        self.stats.encode(writer)?;
 
         Ok(())
     }
@@ -62,21 +183,54 @@ where
 mod test {
     use std::marker::PhantomData;
 
-    use crate::{tests::verify_encode_decode, Header, Kind, Packet, SharedImpl};
+    use crate::{tests::verify_encode_decode, Header, Kind, Packet, Pool, PoolImpl, SharedImpl};
 
-    use super::PingAck;
+    use super::{NodeStats, PingAck};
 
     impl PingAck<SharedImpl> {
-        fn new() -> Self {
+        pub fn new(sent_at: u64, stats: NodeStats) -> Self {
             Self {
                 header: Header::new_test_ack(Kind::PingAck),
+                sent_at,
+                stats,
                 _phantom: PhantomData,
             }
         }
     }
 
+    #[test]
+    fn stats() {
+        let pool = PoolImpl::new(64, 4);
+        let _buffer = pool.acquire("ping");
+        let stats = NodeStats {
+            disk_usage: 90,
+            max_disk_usage: 100,
+            ..NodeStats::default()
+        }
+        .with_pool(&pool.stats());
+
+        assert_eq!(stats.pool_in_use, 1);
+        assert_eq!(stats.pool_capacity, 4);
+        assert_eq!(stats.pool_utilization(), 0.25);
+        assert_eq!(stats.disk_utilization(), 0.9);
+        assert!(stats.is_degraded(0.9));
+        assert!(!stats.is_degraded(0.95));
+        assert!(!NodeStats::default().is_degraded(0.5));
+    }
+
     #[test]
     fn encode_decode() {
-        verify_encode_decode(Packet::PingAck(PingAck::new()));
+        verify_encode_decode(Packet::PingAck(PingAck::new(
+            1_700_000_000_000_000,
+            NodeStats {
+                queues: 3,
+                keys: 1024,
+                disk_usage: 1 << 30,
+                max_disk_usage: 1 << 32,
+                pool_in_use: 12,
+                pool_capacity: 64,
+                in_flight: 7,
+            },
+        )));
     }
 }
@@ -3,11 +3,16 @@ use std::io::{Read, Write};
 use crate::{
     buffer::{Owned, Shared},
     header::{Uuid, Version},
-    ByteStr, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response,
+    ByteStr, Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response,
 };
 
-use super::{Position, ReportAck};
+use super::{Position, ReportAck, Shard};
 
+/// Sent from the `Operator` with a node's position in the chain. The `epoch` goes up with every
+/// change to the chain, so a delayed `Report` from an older configuration can be ignored.
+///
+/// Backends are also told which [`Shard`] their chain serves, frontends get a routing table in
+/// their [`Position`] instead.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[repr(C)]
 pub struct Report<S>
@@ -15,6 +20,8 @@ where
     S: Shared,
 {
     pub(crate) header: Header,
+    pub(crate) epoch: u64,
+    pub(crate) shard: Option<Shard>,
     pub(crate) position: Position<S>,
 }
 
@@ -22,9 +29,17 @@ impl<S> Report<S>
 where
     S: Shared,
 {
-    pub fn new(version: impl Into<Version>, uuid: impl Into<Uuid>, position: Position<S>) -> Self {
+    pub fn new(
+        version: impl Into<Version>,
+        uuid: impl Into<Uuid>,
+        epoch: u64,
+        shard: Option<Shard>,
+        position: Position<S>,
+    ) -> Self {
         Self {
             header: Header::new(Kind::Report, version, uuid, position.encode_len()),
+            epoch,
+            shard,
             position,
         }
     }
@@ -33,10 +48,26 @@ where
         self.header
     }
 
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the shard the node's chain serves, `None` for frontends and observers.
+    pub fn shard(&self) -> Option<Shard> {
+        self.shard
+    }
+
     pub fn position(&self) -> &Position<S> {
         &self.position
     }
 
+    /// Returns `true` if the report is from an older configuration than `epoch`, the latest one
+    /// the node has seen. Stale reports should be nacked with [`STALE_EPOCH`](crate::STALE_EPOCH)
+    /// and their position ignored.
+    pub fn is_stale(&self, epoch: u64) -> bool {
+        self.epoch < epoch
+    }
+
     pub fn ack(self) -> ReportAck<S> {
         ReportAck {
             header: Header::new(Kind::ReportAck, self.header.version, self.header.uuid, 0),
@@ -63,9 +94,16 @@ where
     {
         assert_eq!(header.kind, Kind::Report);
 
+        let epoch = u64::decode(reader)?;
+        let shard = Option::decode(reader)?;
         let position = Position::decode_owned(reader, buffer)?;
 
-        Ok(Self { header, position })
+        Ok(Self {
+            header,
+            epoch,
+            shard,
+            position,
+        })
     }
 }
 
@@ -76,6 +114,8 @@ where
 {
     fn encode(&self, writer: &mut W) -> Result<(), Error> {
         self.header.encode(writer)?;
+        self.epoch.encode(writer)?;
+        self.shard.encode(writer)?;
         self.position.encode(writer)?;
 
         Ok(())
@@ -85,8 +125,9 @@ where
 #[cfg(test)]
 mod test {
     use crate::{
-        buffer::byte_str, system_codec::Position, tests::verify_encode_decode, Ack, Packet,
-        INTERNAL_ERROR, SUCCESS,
+        system_codec::{test_address, Position, Shard, SlotRange},
+        tests::verify_encode_decode,
+        Ack, Packet, SharedImpl, INTERNAL_ERROR, STALE_EPOCH, SUCCESS,
     };
 
     use super::Report;
@@ -96,8 +137,10 @@ mod test {
         let report = Report::new(
             1,
             2,
+            3,
+            None,
             Position::Head {
-                next: byte_str(b"next"),
+                next: test_address(b"next"),
             },
         );
 
@@ -108,13 +151,26 @@ mod test {
         assert_eq!(report_nack.response().code(), INTERNAL_ERROR);
     }
 
+    #[test]
+    fn stale() {
+        let report = Report::<SharedImpl>::new(1, 2, 3, None, Position::Candidate);
+        assert!(!report.is_stale(2));
+        assert!(!report.is_stale(3));
+        assert!(report.is_stale(4));
+
+        let report_nack = report.nack(STALE_EPOCH, None);
+        assert_eq!(report_nack.response().code(), STALE_EPOCH);
+    }
+
     #[test]
     fn encode_decode() {
         verify_encode_decode(Packet::Report(Report::new(
             1,
             2,
+            3,
+            Some(Shard::new(1, SlotRange::ALL)),
             Position::Head {
-                next: byte_str(b"next"),
+                next: test_address(b"next"),
             },
         )));
     }
@@ -0,0 +1,269 @@
+use std::io::{Read, Write};
+
+use crc32fast::Hasher;
+
+use super::Address;
+
+use crate::{
+    buffer::{Owned, Shared},
+    Decode, DecodeOwned, Encode, Error,
+};
+
+/// The number of hash slots keys and queue paths are spread over.
+pub const SLOTS: u16 = 16384;
+
+/// Returns the hash slot of a key or queue path.
+pub fn slot(key: &[u8]) -> u16 {
+    slot_of([key])
+}
+
+pub(crate) fn slot_of<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> u16 {
+    let mut hasher = Hasher::new();
+    for chunk in chunks {
+        hasher.update(chunk);
+    }
+    (hasher.finalize() % u32::from(SLOTS)) as u16
+}
+
+/// A range of hash slots, from `start` up to but not including `end`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct SlotRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl SlotRange {
+    /// Every slot, for a cluster with a single chain.
+    pub const ALL: Self = Self {
+        start: 0,
+        end: SLOTS,
+    };
+
+    pub fn new(start: u16, end: u16) -> Self {
+        Self { start, end }
+    }
+
+    pub fn contains(&self, slot: u16) -> bool {
+        (self.start..self.end).contains(&slot)
+    }
+}
+
+impl<R> Decode<R> for SlotRange
+where
+    R: Read,
+{
+    fn decode(reader: &mut R) -> Result<Self, Error> {
+        let start = u16::decode(reader)?;
+        let end = u16::decode(reader)?;
+        if start > end || end > SLOTS {
+            return Err(Error::SystemBadSlotRange { start, end });
+        }
+
+        Ok(Self { start, end })
+    }
+}
+
+impl<W> Encode<W> for SlotRange
+where
+    W: Write,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.start.encode(writer)?;
+        self.end.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+/// A chain and the hash slots it owns.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Shard {
+    pub id: u32,
+    pub slots: SlotRange,
+}
+
+impl Shard {
+    pub fn new(id: u32, slots: SlotRange) -> Self {
+        Self { id, slots }
+    }
+
+    pub fn owns(&self, slot: u16) -> bool {
+        self.slots.contains(slot)
+    }
+}
+
+impl<R> Decode<R> for Shard
+where
+    R: Read,
+{
+    fn decode(reader: &mut R) -> Result<Self, Error> {
+        let id = u32::decode(reader)?;
+        let slots = SlotRange::decode(reader)?;
+
+        Ok(Self { id, slots })
+    }
+}
+
+impl<W> Encode<W> for Shard
+where
+    W: Write,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.id.encode(writer)?;
+        self.slots.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+/// An entry in a frontend's routing table: where to send writes and reads for a shard.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Route<S>
+where
+    S: Shared,
+{
+    pub shard: Shard,
+    pub head: Option<Address<S>>,
+    pub tail: Option<Address<S>>,
+}
+
+impl<S> Route<S>
+where
+    S: Shared,
+{
+    pub fn new(shard: Shard, head: Option<Address<S>>, tail: Option<Address<S>>) -> Self {
+        Self { shard, head, tail }
+    }
+
+    pub fn encode_len(&self) -> usize {
+        self.head.as_ref().map(|h| h.encode_len()).unwrap_or(0)
+            + self.tail.as_ref().map(|t| t.encode_len()).unwrap_or(0)
+    }
+}
+
+impl<W, S> Encode<W> for Route<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.shard.encode(writer)?;
+        self.head.encode(writer)?;
+        self.tail.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+impl<R, O> DecodeOwned<R, O> for Route<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode_owned(reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let shard = Shard::decode(reader)?;
+        let head = Option::decode_owned(reader, buffer)?;
+        let tail = Option::decode_owned(reader, buffer)?;
+
+        Ok(Self { shard, head, tail })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use matches::assert_matches;
+
+    use crate::{
+        buffer::{binary_data, byte_str},
+        deque_codec::{Create, Delete as DeleteQueue, Dequeue, Enqueue, Len, Peek},
+        kv_store_codec::{Delete, Get, Put},
+        Decode, Encode, Error, Packet, SharedImpl,
+    };
+
+    use super::{super::Ping, slot, slot_of, Shard, SlotRange, SLOTS};
+
+    #[test]
+    fn slots() {
+        assert!(slot(b"kitty") < SLOTS);
+        assert_eq!(
+            slot(b"kitty"),
+            slot_of([b"ki".as_slice(), b"tty".as_slice()])
+        );
+        assert_eq!(slot(b""), 0);
+
+        let shard = Shard::new(1, SlotRange::new(0, 100));
+        assert!(shard.owns(0));
+        assert!(shard.owns(99));
+        assert!(!shard.owns(100));
+        assert!(SlotRange::ALL.contains(SLOTS - 1));
+    }
+
+    #[test]
+    fn decode_slot_range() {
+        for range in [
+            SlotRange::ALL,
+            SlotRange::new(0, 0),
+            SlotRange::new(100, 200),
+        ] {
+            let mut bytes = vec![];
+            range.encode(&mut bytes).expect("encode");
+            assert_eq!(
+                SlotRange::decode(&mut bytes.as_slice()).expect("decode"),
+                range
+            );
+        }
+
+        for (start, end) in [(200, 100), (0, SLOTS + 1)] {
+            let mut bytes = vec![];
+            SlotRange::new(start, end)
+                .encode(&mut bytes)
+                .expect("encode");
+            assert_matches!(
+                SlotRange::decode(&mut bytes.as_slice()),
+                Err(Error::SystemBadSlotRange { start: s, end: e }) if s == start && e == end
+            );
+        }
+    }
+
+    #[test]
+    fn packet_slots() {
+        let path = byte_str(b"/tmp/kitties");
+        let key = binary_data(b"kitty");
+        for (packet, expected) in [
+            (
+                Packet::Enqueue(Enqueue::new(1, 1, path.clone(), binary_data(b"purr"))),
+                b"/tmp/kitties".as_slice(),
+            ),
+            (
+                Packet::Dequeue(Dequeue::new(1, 1, path.clone())),
+                b"/tmp/kitties",
+            ),
+            (
+                Packet::Peek(Peek::new(1, 1, path.clone(), 0)),
+                b"/tmp/kitties",
+            ),
+            (Packet::Len(Len::new(1, 1, path.clone())), b"/tmp/kitties"),
+            (
+                Packet::CreateQueue(Create::new(1, 1, path.clone(), 1024, 1024)),
+                b"/tmp/kitties",
+            ),
+            (
+                Packet::DeleteQueue(DeleteQueue::new(1, 1, path)),
+                b"/tmp/kitties",
+            ),
+            (
+                Packet::Put(Put::new(1, 1, key.clone(), binary_data(b"meow"))),
+                b"kitty",
+            ),
+            (Packet::Get(Get::new(1, 1, key.clone())), b"kitty"),
+            (Packet::Delete(Delete::new(1, 1, key)), b"kitty"),
+        ] {
+            assert_eq!(packet.slot(), Some(slot(expected)), "{packet:?}");
+        }
+
+        assert_eq!(Packet::Ping(Ping::<SharedImpl>::new(1, 1)).slot(), None);
+    }
+}
@@ -1,4 +1,6 @@
-use std::io::{Read, Write};
+use std::io::{self, ErrorKind, Read, Write};
+
+use crc32fast::Hasher;
 
 use crate::{
     buffer::{BinaryData, ByteStr, Owned, Shared},
@@ -8,6 +10,14 @@ use crate::{
 
 use super::TransferAck;
 
+/// A chunk of a file sent to a candidate during catch-up.
+///
+/// `offset` is where the content starts in the file and `total` is the size of the whole file.
+/// The checksum is a CRC-32 of the content, it is written after the content so it can be computed
+/// while streaming.
+///
+/// Decoding does not check the chunk, so a bad one can still be nacked. Call [`Transfer::verify`]
+/// before writing the content.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[repr(C)]
 pub struct Transfer<S>
@@ -15,27 +25,44 @@ where
     S: Shared,
 {
     pub(crate) header: Header,
+    pub(crate) session: u128,
     pub(crate) path: ByteStr<S>,
     pub(crate) offset: u64,
+    pub(crate) total: u64,
+    pub(crate) chunk: u32,
+    pub(crate) last: bool,
     pub(crate) content: BinaryData<S>,
+    pub(crate) checksum: u32,
 }
 
 impl<S> Transfer<S>
 where
     S: Shared,
 {
+    /// Creates chunk number `chunk` of a file of `total` bytes. It is the last chunk if the content
+    /// reaches the end of the file.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         version: impl Into<Version>,
         uuid: impl Into<Uuid>,
+        session: u128,
         path: ByteStr<S>,
         offset: u64,
+        total: u64,
+        chunk: u32,
         content: BinaryData<S>,
     ) -> Self {
+        let checksum = checksum(content.data().chunks());
         Self {
             header: Header::new(Kind::Transfer, version, uuid, path.len() + content.len()),
+            session,
             path,
             offset,
+            total,
+            chunk,
+            last: offset.saturating_add(content.len() as u64) == total,
             content,
+            checksum,
         }
     }
 
@@ -43,6 +70,11 @@ where
         self.header
     }
 
+    /// Returns the id of the session started by a [`TransferBegin`](super::TransferBegin).
+    pub fn session(&self) -> u128 {
+        self.session
+    }
+
     pub fn path(&self) -> &ByteStr<S> {
         &self.path
     }
@@ -51,22 +83,70 @@ where
         self.offset
     }
 
+    /// Returns the size of the whole file.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the index of the chunk in the file, starting at 0.
+    pub fn chunk(&self) -> u32 {
+        self.chunk
+    }
+
+    /// Returns `true` if this is the last chunk of the file.
+    pub fn is_last(&self) -> bool {
+        self.last
+    }
+
     pub fn content(&self) -> &BinaryData<S> {
         &self.content
     }
 
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    /// Returns the offset the next chunk starts at.
+    pub fn next_offset(&self) -> u64 {
+        self.offset.saturating_add(self.content.len() as u64)
+    }
+
+    /// Checks that the content fits in the file, is the last chunk only if it reaches the end of
+    /// the file, and matches the checksum.
+    pub fn verify(&self) -> Result<(), Error> {
+        check_bounds(self.offset, self.content.len(), self.total)?;
+        check_last(self.offset, self.content.len(), self.total, self.last)?;
+        check_checksum(self.checksum, checksum(self.content.data().chunks()))
+    }
+
+    /// Acks the chunk, asking for the one after it.
     pub fn ack(self) -> TransferAck<S> {
-        TransferAck {
-            header: Header::new(Kind::TransferAck, self.header.version, self.header.uuid, 1),
-            response: Response::success(),
-        }
+        let next_offset = self.next_offset();
+        transfer_ack(&self.header, next_offset, Response::success())
     }
 
+    /// Nacks the chunk, asking for it to be sent again.
     pub fn nack(self, response_code: u8, reason: Option<ByteStr<S>>) -> TransferAck<S> {
-        TransferAck {
-            header: Header::new(Kind::TransferAck, self.header.version, self.header.uuid, 1),
-            response: Response::fail(response_code, reason),
-        }
+        transfer_ack(
+            &self.header,
+            self.offset,
+            Response::fail(response_code, reason),
+        )
+    }
+
+    /// Nacks the chunk and asks for the file to be resumed from `next_offset`, for example when a
+    /// restarted candidate only has part of the file.
+    pub fn resume_from(
+        self,
+        next_offset: u64,
+        response_code: u8,
+        reason: Option<ByteStr<S>>,
+    ) -> TransferAck<S> {
+        transfer_ack(
+            &self.header,
+            next_offset,
+            Response::fail(response_code, reason),
+        )
     }
 }
 
@@ -81,15 +161,25 @@ where
     {
         assert_eq!(header.kind, Kind::Transfer);
 
+        let session = u128::decode(reader)?;
         let path = ByteStr::decode_owned(reader, buffer)?;
         let offset = u64::decode(reader)?;
+        let total = u64::decode(reader)?;
+        let chunk = u32::decode(reader)?;
+        let last = u8::decode(reader)? > 0;
         let content = BinaryData::decode_owned(reader, buffer)?;
+        let checksum = u32::decode(reader)?;
 
         Ok(Self {
             header,
+            session,
             path,
             offset,
+            total,
+            chunk,
+            last,
             content,
+            checksum,
         })
     }
 }
@@ -101,39 +191,386 @@ where
 {
     fn encode(&self, writer: &mut W) -> Result<(), Error> {
         self.header.encode(writer)?;
+        self.session.encode(writer)?;
         self.path.encode(writer)?;
         self.offset.encode(writer)?;
+        self.total.encode(writer)?;
+        self.chunk.encode(writer)?;
+        u8::from(self.last).encode(writer)?;
         self.content.encode(writer)?;
+        self.checksum.encode(writer)?;
 
         Ok(())
     }
 }
 
+/// A [`Transfer`] whose content is streamed instead of held in a buffer, so large files can be sent
+/// and received without going through the pool. It is the same packet on the wire.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamedTransfer<S>
+where
+    S: Shared,
+{
+    header: Header,
+    session: u128,
+    path: ByteStr<S>,
+    offset: u64,
+    total: u64,
+    chunk: u32,
+    last: bool,
+    len: usize,
+}
+
+impl<S> StreamedTransfer<S>
+where
+    S: Shared,
+{
+    /// Creates a transfer of `len` bytes of content, which is read from a source when encoding.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        version: impl Into<Version>,
+        uuid: impl Into<Uuid>,
+        session: u128,
+        path: ByteStr<S>,
+        offset: u64,
+        total: u64,
+        chunk: u32,
+        len: usize,
+    ) -> Self {
+        Self {
+            header: Header::new(Kind::Transfer, version, uuid, path.len() + len),
+            session,
+            path,
+            offset,
+            total,
+            chunk,
+            last: offset.saturating_add(len as u64) == total,
+            len,
+        }
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Returns the id of the session started by a [`TransferBegin`](super::TransferBegin).
+    pub fn session(&self) -> u128 {
+        self.session
+    }
+
+    pub fn path(&self) -> &ByteStr<S> {
+        &self.path
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the size of the whole file.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the index of the chunk in the file, starting at 0.
+    pub fn chunk(&self) -> u32 {
+        self.chunk
+    }
+
+    /// Returns `true` if this is the last chunk of the file.
+    pub fn is_last(&self) -> bool {
+        self.last
+    }
+
+    /// Returns the length of the content.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the offset the next chunk starts at.
+    pub fn next_offset(&self) -> u64 {
+        self.offset.saturating_add(self.len as u64)
+    }
+
+    /// Encodes the transfer, copying exactly `len` bytes of content from `source`.
+    /// Returns the checksum of the content.
+    pub fn encode_from<R, W>(&self, source: &mut R, writer: &mut W) -> Result<u32, Error>
+    where
+        R: Read,
+        W: Write,
+    {
+        self.header.encode(writer)?;
+        self.session.encode(writer)?;
+        self.path.encode(writer)?;
+        self.offset.encode(writer)?;
+        self.total.encode(writer)?;
+        self.chunk.encode(writer)?;
+        u8::from(self.last).encode(writer)?;
+        self.len.encode(writer)?;
+
+        let checksum = copy_checksummed(source, writer, self.len).map_err(Error::Encode)?;
+        checksum.encode(writer)?;
+
+        Ok(checksum)
+    }
+
+    /// Decodes a transfer after its header, copying the content to `sink` instead of `buffer`.
+    /// Only the path is decoded into `buffer`.
+    ///
+    /// The content length must match the header, the content must fit in the file, be the last chunk
+    /// only if it reaches the end of the file, and match the checksum. The content has already been
+    /// written to `sink` when the checksum is checked.
+    pub fn decode_to<R, O, W>(
+        header: Header,
+        reader: &mut R,
+        buffer: &mut O,
+        sink: &mut W,
+    ) -> Result<Self, Error>
+    where
+        R: Read,
+        O: Owned<Shared = S>,
+        W: Write,
+    {
+        assert_eq!(header.kind, Kind::Transfer);
+
+        let session = u128::decode(reader)?;
+        let path = ByteStr::decode_owned(reader, buffer)?;
+        let offset = u64::decode(reader)?;
+        let total = u64::decode(reader)?;
+        let chunk = u32::decode(reader)?;
+        let last = u8::decode(reader)? > 0;
+        let len = usize::decode(reader)?;
+        if path.len().checked_add(len) != Some(header.len) {
+            return Err(Error::TransferLength {
+                len: header.len,
+                path: path.len(),
+                content: len,
+            });
+        }
+        check_bounds(offset, len, total)?;
+        check_last(offset, len, total, last)?;
+
+        let actual = copy_checksummed(reader, sink, len).map_err(Error::Decode)?;
+        check_checksum(u32::decode(reader)?, actual)?;
+
+        Ok(Self {
+            header,
+            session,
+            path,
+            offset,
+            total,
+            chunk,
+            last,
+            len,
+        })
+    }
+
+    /// Acks the chunk, asking for the one after it.
+    pub fn ack(self) -> TransferAck<S> {
+        let next_offset = self.next_offset();
+        transfer_ack(&self.header, next_offset, Response::success())
+    }
+
+    /// Nacks the chunk, asking for it to be sent again.
+    pub fn nack(self, response_code: u8, reason: Option<ByteStr<S>>) -> TransferAck<S> {
+        transfer_ack(
+            &self.header,
+            self.offset,
+            Response::fail(response_code, reason),
+        )
+    }
+}
+
+fn transfer_ack<S>(header: &Header, next_offset: u64, response: Response<S>) -> TransferAck<S>
+where
+    S: Shared,
+{
+    TransferAck {
+        header: Header::new(Kind::TransferAck, header.version, header.uuid, 1),
+        response,
+        next_offset,
+    }
+}
+
+fn checksum<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> u32 {
+    let mut hasher = Hasher::new();
+    for chunk in chunks {
+        hasher.update(chunk);
+    }
+    hasher.finalize()
+}
+
+fn check_checksum(expected: u32, actual: u32) -> Result<(), Error> {
+    if expected != actual {
+        return Err(Error::TransferChecksum { expected, actual });
+    }
+    Ok(())
+}
+
+fn check_bounds(offset: u64, len: usize, total: u64) -> Result<(), Error> {
+    match offset.checked_add(len as u64) {
+        Some(end) if end <= total => Ok(()),
+        _ => Err(Error::TransferOffset {
+            offset,
+            content: len,
+            total,
+        }),
+    }
+}
+
+// only called after `check_bounds`, so the end doesn't overflow.
+fn check_last(offset: u64, len: usize, total: u64, last: bool) -> Result<(), Error> {
+    let end = offset + len as u64;
+    if last != (end == total) {
+        return Err(Error::TransferLast { last, end, total });
+    }
+    Ok(())
+}
+
+/// Copies exactly `len` bytes from `reader` to `writer` and returns their checksum.
+fn copy_checksummed<R, W>(reader: &mut R, writer: &mut W, len: usize) -> io::Result<u32>
+where
+    R: Read,
+    W: Write,
+{
+    let mut hasher = Hasher::new();
+    let mut chunk = [0; 8 * 1024];
+    let mut remaining = len;
+    while remaining > 0 {
+        let read = reader.read(&mut chunk[..remaining.min(8 * 1024)])?;
+        if read == 0 {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("transfer ended after {} of {} bytes", len - remaining, len),
+            ));
+        }
+        hasher.update(&chunk[..read]);
+        writer.write_all(&chunk[..read])?;
+        remaining -= read;
+    }
+    Ok(hasher.finalize())
+}
+
 #[cfg(test)]
 mod test {
+    use std::io::Cursor;
+
+    use matches::assert_matches;
+
     use crate::{
         buffer::{binary_data, byte_str},
         tests::verify_encode_decode,
-        Ack, Packet, INTERNAL_ERROR, SUCCESS,
+        Ack, Decode, Encode, Error, Header, Packet, Pool, PoolImpl, INTERNAL_ERROR, SUCCESS,
     };
 
-    use super::Transfer;
+    use super::{StreamedTransfer, Transfer};
 
     #[test]
     fn acks() {
         let transfer = Transfer::new(
             1,
             2,
+            7,
             byte_str(b"/tmp/kitty"),
-            0,
+            8,
+            16,
+            2,
             binary_data(&[0x01, 0x02, 0x03, 0x04]),
         );
 
         let ack = transfer.clone().ack();
         assert_eq!(ack.response().code(), SUCCESS);
+        assert_eq!(ack.next_offset(), 12);
 
-        let nack = transfer.nack(INTERNAL_ERROR, None);
+        let nack = transfer.clone().nack(INTERNAL_ERROR, None);
         assert_eq!(nack.response().code(), INTERNAL_ERROR);
+        assert_eq!(nack.next_offset(), 8);
+
+        let resume = transfer.resume_from(4, INTERNAL_ERROR, None);
+        assert_eq!(resume.response().code(), INTERNAL_ERROR);
+        assert_eq!(resume.next_offset(), 4);
+    }
+
+    #[test]
+    fn chunks() {
+        let first = Transfer::new(
+            1,
+            2,
+            7,
+            byte_str(b"/tmp/kitty"),
+            0,
+            6,
+            0,
+            binary_data(&[1, 2]),
+        );
+        assert!(!first.is_last());
+        assert_eq!(first.next_offset(), 2);
+        first.verify().expect("verify");
+
+        let last = Transfer::new(
+            1,
+            2,
+            7,
+            byte_str(b"/tmp/kitty"),
+            2,
+            6,
+            1,
+            binary_data(&[3; 4]),
+        );
+        assert!(last.is_last());
+        assert_eq!(last.chunk(), 1);
+        assert_eq!(last.total(), 6);
+        last.verify().expect("verify");
+
+        let mut corrupt = last.clone();
+        corrupt.checksum ^= 1;
+        assert_matches!(corrupt.verify(), Err(Error::TransferChecksum { .. }));
+
+        // claims to be last with bytes still missing.
+        let mut early = first.clone();
+        early.last = true;
+        assert_matches!(
+            early.verify(),
+            Err(Error::TransferLast {
+                last: true,
+                end: 2,
+                total: 6
+            })
+        );
+
+        // reaches the end without claiming to be last.
+        let mut unfinished = last.clone();
+        unfinished.last = false;
+        assert_matches!(
+            unfinished.verify(),
+            Err(Error::TransferLast {
+                last: false,
+                end: 6,
+                total: 6
+            })
+        );
+
+        let past_end = Transfer::new(
+            1,
+            2,
+            7,
+            byte_str(b"/tmp/kitty"),
+            4,
+            6,
+            1,
+            binary_data(&[3; 4]),
+        );
+        assert_matches!(
+            past_end.verify(),
+            Err(Error::TransferOffset {
+                offset: 4,
+                content: 4,
+                total: 6
+            })
+        );
     }
 
     #[test]
@@ -141,9 +578,154 @@ mod test {
         verify_encode_decode(Packet::Transfer(Transfer::new(
             1,
             2,
+            7,
             byte_str(b"/tmp/kitty"),
             42,
+            46,
+            3,
             binary_data(&[0x01, 0x02, 0x03, 0x04]),
         )));
     }
+
+    #[test]
+    fn encode_from_source() {
+        let content = (0..=255).cycle().take(4096).collect::<Vec<u8>>();
+        let streamed =
+            StreamedTransfer::new(1, 2, 7, byte_str(b"/tmp/kitty"), 42, 8192, 1, content.len());
+        let mut bytes = vec![];
+        let checksum = streamed
+            .encode_from(&mut content.as_slice(), &mut bytes)
+            .expect("encode");
+
+        let transfer = Transfer::new(
+            1,
+            2,
+            7,
+            byte_str(b"/tmp/kitty"),
+            42,
+            8192,
+            1,
+            binary_data(&content),
+        );
+        assert_eq!(checksum, transfer.checksum());
+        let mut expected = vec![];
+        transfer.encode(&mut expected).expect("encode");
+        assert_eq!(bytes, expected);
+
+        // the content does not go through the pool, so one small block is enough.
+        let pool = PoolImpl::new(64, 1);
+        let mut buffer = pool.acquire("transfer");
+        let mut reader = Cursor::new(bytes);
+        let header = Header::decode(&mut reader).expect("header");
+        let mut sink = vec![];
+        let decoded = StreamedTransfer::decode_to(header, &mut reader, &mut buffer, &mut sink)
+            .expect("decode");
+        assert_eq!(decoded, streamed);
+        assert_eq!(decoded.next_offset(), 4138);
+        assert!(!decoded.is_last());
+        assert_eq!(sink, content);
+
+        let err = streamed
+            .encode_from(&mut &content[..10], &mut vec![])
+            .unwrap_err();
+        assert_matches!(err, Error::Encode(_));
+    }
+
+    #[test]
+    fn decode_to_checks_header() {
+        let transfer = Transfer::new(
+            1,
+            2,
+            7,
+            byte_str(b"/tmp/kitty"),
+            0,
+            3,
+            0,
+            binary_data(&[1, 2, 3]),
+        );
+        let mut bytes = vec![];
+        transfer.encode(&mut bytes).expect("encode");
+        let pool = PoolImpl::new(64, 1);
+        let mut buffer = pool.acquire("transfer");
+
+        let mut reader = Cursor::new(&bytes);
+        let mut header = Header::decode(&mut reader).expect("header");
+        header.len += 1;
+        let err =
+            StreamedTransfer::decode_to(header, &mut reader, &mut buffer, &mut vec![]).unwrap_err();
+        assert_matches!(
+            err,
+            Error::TransferLength {
+                len: 14,
+                path: 10,
+                content: 3
+            }
+        );
+
+        // the checksum is the last 4 bytes.
+        let mut corrupt = bytes.clone();
+        let content = corrupt.len() - 5;
+        corrupt[content] ^= 1;
+        let mut reader = Cursor::new(&corrupt);
+        let header = Header::decode(&mut reader).expect("header");
+        let err =
+            StreamedTransfer::decode_to(header, &mut reader, &mut buffer, &mut vec![]).unwrap_err();
+        assert_matches!(err, Error::TransferChecksum { .. });
+
+        let past_end = Transfer::new(
+            1,
+            2,
+            7,
+            byte_str(b"/tmp/kitty"),
+            u64::MAX,
+            0,
+            0,
+            binary_data(&[1]),
+        );
+        let mut bytes = vec![];
+        past_end.encode(&mut bytes).expect("encode");
+        let mut reader = Cursor::new(&bytes);
+        let header = Header::decode(&mut reader).expect("header");
+        let err =
+            StreamedTransfer::decode_to(header, &mut reader, &mut buffer, &mut vec![]).unwrap_err();
+        assert_matches!(err, Error::TransferOffset { .. });
+
+        let mut bytes = vec![];
+        transfer.encode(&mut bytes).expect("encode");
+        let mut reader = Cursor::new(&bytes[..bytes.len() - 6]);
+        let header = Header::decode(&mut reader).expect("header");
+        let err =
+            StreamedTransfer::decode_to(header, &mut reader, &mut buffer, &mut vec![]).unwrap_err();
+        assert_matches!(err, Error::Decode(_));
+    }
+
+    #[test]
+    fn decode_to_checks_last() {
+        let pool = PoolImpl::new(64, 1);
+        let mut buffer = pool.acquire("transfer");
+        for (offset, last, end) in [(0, true, 3), (3, false, 6)] {
+            let mut transfer = Transfer::new(
+                1,
+                2,
+                7,
+                byte_str(b"/tmp/kitty"),
+                offset,
+                6,
+                0,
+                binary_data(&[1, 2, 3]),
+            );
+            transfer.last = last;
+            let mut bytes = vec![];
+            transfer.encode(&mut bytes).expect("encode");
+
+            let mut reader = Cursor::new(&bytes);
+            let header = Header::decode(&mut reader).expect("header");
+            let err = StreamedTransfer::decode_to(header, &mut reader, &mut buffer, &mut vec![])
+                .unwrap_err();
+            assert_matches!(
+                err,
+                Error::TransferLast { last: l, end: e, total: 6 } if l == last && e == end
+            );
+        }
+    }
 }
@@ -1,7 +1,8 @@
 use std::io::{Read, Write};
 
 use crate::{
-    buffer::Owned, Ack, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response, Shared,
+    buffer::Owned, Ack, Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response,
+    Shared,
 };
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -12,6 +13,17 @@ where
 {
     pub(crate) header: Header,
     pub(crate) response: Response<S>,
+    pub(crate) next_offset: u64,
+}
+
+impl<S> TransferAck<S>
+where
+    S: Shared,
+{
+    /// Returns the offset the receiver expects the next chunk to start at.
+    pub fn next_offset(&self) -> u64 {
+        self.next_offset
+    }
 }
 
 impl<R, O> PartialDecode<R, O> for TransferAck<O::Shared>
@@ -26,8 +38,13 @@ where
         assert_eq!(header.kind, Kind::TransferAck);
 
         let response = Response::decode_owned(reader, buffer)?;
+        let next_offset = u64::decode(reader)?;
 
-        Ok(Self { header, response })
+        Ok(Self {
+            header,
+            response,
+            next_offset,
+        })
     }
 }
 
@@ -39,6 +56,7 @@ where
     fn encode(&self, writer: &mut W) -> Result<(), Error> {
         self.header.encode(writer)?;
         self.response.encode(writer)?;
+        self.next_offset.encode(writer)?;
 
         Ok(())
     }
@@ -64,16 +82,20 @@ mod test {
     use super::TransferAck;
 
     impl TransferAck<SharedImpl> {
-        pub fn new(response: Response<SharedImpl>) -> Self {
+        pub fn new(response: Response<SharedImpl>, next_offset: u64) -> Self {
             Self {
                 header: Header::new_test_ack(Kind::TransferAck),
                 response,
+                next_offset,
             }
         }
     }
 
     #[test]
     fn encode_decode() {
-        verify_encode_decode(Packet::TransferAck(TransferAck::new(Response::success())));
+        verify_encode_decode(Packet::TransferAck(TransferAck::new(
+            Response::success(),
+            42,
+        )));
     }
 }
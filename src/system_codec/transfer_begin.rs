@@ -0,0 +1,264 @@
+use std::io::{Read, Write};
+
+use crate::{
+    buffer::{ByteStr, Owned, Shared},
+    header::{Uuid, Version},
+    Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response,
+};
+
+use super::TransferBeginAck;
+
+/// A file that will be sent during a transfer session.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ManifestEntry<S>
+where
+    S: Shared,
+{
+    pub path: ByteStr<S>,
+    /// The size of the whole file.
+    pub size: u64,
+    /// The CRC-32 of the whole file.
+    pub checksum: u32,
+}
+
+impl<S> ManifestEntry<S>
+where
+    S: Shared,
+{
+    pub fn new(path: ByteStr<S>, size: u64, checksum: u32) -> Self {
+        Self {
+            path,
+            size,
+            checksum,
+        }
+    }
+}
+
+impl<W, S> Encode<W> for ManifestEntry<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.path.encode(writer)?;
+        self.size.encode(writer)?;
+        self.checksum.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+impl<R, O> DecodeOwned<R, O> for ManifestEntry<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode_owned(reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let path = ByteStr::decode_owned(reader, buffer)?;
+        let size = u64::decode(reader)?;
+        let checksum = u32::decode(reader)?;
+
+        Ok(Self {
+            path,
+            size,
+            checksum,
+        })
+    }
+}
+
+/// Starts a transfer session, listing every file that will be sent before any data flows so the
+/// candidate can allocate disk and track progress. Every `Transfer` of the session carries its id.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct TransferBegin<S>
+where
+    S: Shared,
+{
+    pub(crate) header: Header,
+    pub(crate) session: u128,
+    pub(crate) manifest: Vec<ManifestEntry<S>>,
+}
+
+impl<S> TransferBegin<S>
+where
+    S: Shared,
+{
+    pub fn new(
+        version: impl Into<Version>,
+        uuid: impl Into<Uuid>,
+        session: u128,
+        manifest: Vec<ManifestEntry<S>>,
+    ) -> Self {
+        let len = manifest.iter().map(|entry| entry.path.len()).sum();
+        Self {
+            header: Header::new(Kind::TransferBegin, version, uuid, len),
+            session,
+            manifest,
+        }
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    pub fn session(&self) -> u128 {
+        self.session
+    }
+
+    pub fn manifest(&self) -> &[ManifestEntry<S>] {
+        &self.manifest
+    }
+
+    /// Returns the number of bytes the session will transfer.
+    pub fn total_size(&self) -> u64 {
+        self.manifest.iter().map(|entry| entry.size).sum()
+    }
+
+    /// Returns the manifest entry for `path`.
+    pub fn entry(&self, path: &[u8]) -> Option<&ManifestEntry<S>> {
+        self.manifest
+            .iter()
+            .find(|entry| entry.path.as_slice() == path)
+    }
+
+    pub fn ack(self) -> TransferBeginAck<S> {
+        TransferBeginAck {
+            header: Header::new(
+                Kind::TransferBeginAck,
+                self.header.version,
+                self.header.uuid,
+                0,
+            ),
+            response: Response::success(),
+            session: self.session,
+        }
+    }
+
+    pub fn nack(self, response_code: u8, reason: Option<ByteStr<S>>) -> TransferBeginAck<S> {
+        TransferBeginAck {
+            header: Header::new(
+                Kind::TransferBeginAck,
+                self.header.version,
+                self.header.uuid,
+                0,
+            ),
+            response: Response::fail(response_code, reason),
+            session: self.session,
+        }
+    }
+}
+
+impl<R, O> PartialDecode<R, O> for TransferBegin<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode(header: Header, reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        assert_eq!(header.kind, Kind::TransferBegin);
+
+        let session = u128::decode(reader)?;
+        let manifest = Vec::decode_owned(reader, buffer)?;
+
+        Ok(Self {
+            header,
+            session,
+            manifest,
+        })
+    }
+}
+
+impl<W, S> Encode<W> for TransferBegin<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.header.encode(writer)?;
+        self.session.encode(writer)?;
+        self.manifest.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use matches::assert_matches;
+
+    use crate::{
+        buffer::{byte_str, Pool, PoolImpl},
+        full_decode,
+        tests::verify_encode_decode,
+        Ack, Encode, Error, Header, Kind, Packet, INTERNAL_ERROR, SUCCESS,
+    };
+
+    use super::{ManifestEntry, TransferBegin};
+
+    fn manifest() -> Vec<ManifestEntry<crate::SharedImpl>> {
+        vec![
+            ManifestEntry::new(byte_str(b"/data/store.db"), 4096, 0xdeadbeef),
+            ManifestEntry::new(byte_str(b"/data/store.log"), 100, 0x1234),
+        ]
+    }
+
+    #[test]
+    fn manifest_totals() {
+        let begin = TransferBegin::new(1, 2, 7, manifest());
+        assert_eq!(begin.session(), 7);
+        assert_eq!(begin.manifest().len(), 2);
+        assert_eq!(begin.total_size(), 4196);
+        assert_eq!(
+            begin.entry(b"/data/store.log").map(|entry| entry.size),
+            Some(100)
+        );
+        assert!(begin.entry(b"/data/missing").is_none());
+    }
+
+    #[test]
+    fn acks() {
+        let begin = TransferBegin::new(1, 2, 7, manifest());
+
+        let ack = begin.clone().ack();
+        assert_eq!(ack.response().code(), SUCCESS);
+        assert_eq!(ack.session(), 7);
+
+        let nack = begin.nack(INTERNAL_ERROR, None);
+        assert_eq!(nack.response().code(), INTERNAL_ERROR);
+        assert_eq!(nack.session(), 7);
+    }
+
+    #[test]
+    fn encode_decode() {
+        verify_encode_decode(Packet::TransferBegin(TransferBegin::new(
+            1,
+            2,
+            7,
+            manifest(),
+        )));
+        verify_encode_decode(Packet::TransferBegin(TransferBegin::new(1, 2, 7, vec![])));
+    }
+
+    #[test]
+    fn decode_huge_manifest_count() {
+        // a count off the wire must not be trusted to size the manifest up front.
+        let mut bytes = vec![];
+        Header::new(Kind::TransferBegin, 1, 2, 0)
+            .encode(&mut bytes)
+            .expect("header");
+        7u128.encode(&mut bytes).expect("session");
+        usize::MAX.encode(&mut bytes).expect("count");
+
+        let pool = PoolImpl::new(1024, 1);
+        let mut buffer = pool.acquire("transfer begin");
+        assert_matches!(
+            full_decode(&mut bytes.as_slice(), &mut buffer, None),
+            Err(Error::Decode(_))
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::io::{Read, Write};
+
+use crate::{
+    buffer::Owned, Ack, Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response,
+    Shared,
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct TransferBeginAck<S>
+where
+    S: Shared,
+{
+    pub(crate) header: Header,
+    pub(crate) response: Response<S>,
+    pub(crate) session: u128,
+}
+
+impl<S> TransferBeginAck<S>
+where
+    S: Shared,
+{
+    pub fn session(&self) -> u128 {
+        self.session
+    }
+}
+
+impl<R, O> PartialDecode<R, O> for TransferBeginAck<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode(header: Header, reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        assert_eq!(header.kind, Kind::TransferBeginAck);
+
+        let response = Response::decode_owned(reader, buffer)?;
+        let session = u128::decode(reader)?;
+
+        Ok(Self {
+            header,
+            response,
+            session,
+        })
+    }
+}
+
+impl<W, S> Encode<W> for TransferBeginAck<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.header.encode(writer)?;
+        self.response.encode(writer)?;
+        self.session.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+impl<S> Ack<S> for TransferBeginAck<S>
+where
+    S: Shared,
+{
+    fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn response(&self) -> Response<S> {
+        self.response.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{tests::verify_encode_decode, Header, Kind, Packet, Response, SharedImpl};
+
+    use super::TransferBeginAck;
+
+    impl TransferBeginAck<SharedImpl> {
+        pub fn new(response: Response<SharedImpl>, session: u128) -> Self {
+            Self {
+                header: Header::new_test_ack(Kind::TransferBeginAck),
+                response,
+                session,
+            }
+        }
+    }
+
+    #[test]
+    fn encode_decode() {
+        verify_encode_decode(Packet::TransferBeginAck(TransferBeginAck::new(
+            Response::success(),
+            7,
+        )));
+    }
+}
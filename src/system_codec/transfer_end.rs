@@ -0,0 +1,146 @@
+use std::{
+    io::{Read, Write},
+    marker::PhantomData,
+};
+
+use crate::{
+    buffer::{ByteStr, Owned, Shared},
+    header::{Uuid, Version},
+    Decode, Encode, Error, Header, Kind, PartialDecode, Response,
+};
+
+use super::TransferEndAck;
+
+/// Ends a transfer session once every chunk has been sent. The candidate acks it once it has
+/// checked every file in the manifest against its size and checksum.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct TransferEnd<S>
+where
+    S: Shared,
+{
+    pub(crate) header: Header,
+    pub(crate) session: u128,
+    _phantom: PhantomData<S>,
+}
+
+impl<S> TransferEnd<S>
+where
+    S: Shared,
+{
+    pub fn new(version: impl Into<Version>, uuid: impl Into<Uuid>, session: u128) -> Self {
+        Self {
+            header: Header::new(Kind::TransferEnd, version, uuid, 0),
+            session,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    pub fn session(&self) -> u128 {
+        self.session
+    }
+
+    /// Confirms that every file in the manifest arrived intact.
+    pub fn ack(self) -> TransferEndAck<S> {
+        self.respond(Response::success(), vec![])
+    }
+
+    pub fn nack(self, response_code: u8, reason: Option<ByteStr<S>>) -> TransferEndAck<S> {
+        self.respond(Response::fail(response_code, reason), vec![])
+    }
+
+    /// Reports the files in the manifest that are missing or do not match their size or checksum.
+    pub fn incomplete(
+        self,
+        failed: Vec<ByteStr<S>>,
+        response_code: u8,
+        reason: Option<ByteStr<S>>,
+    ) -> TransferEndAck<S> {
+        self.respond(Response::fail(response_code, reason), failed)
+    }
+
+    fn respond(self, response: Response<S>, failed: Vec<ByteStr<S>>) -> TransferEndAck<S> {
+        let len = failed.iter().map(ByteStr::len).sum();
+        TransferEndAck {
+            header: Header::new(
+                Kind::TransferEndAck,
+                self.header.version,
+                self.header.uuid,
+                len,
+            ),
+            response,
+            session: self.session,
+            failed,
+        }
+    }
+}
+
+impl<R, O> PartialDecode<R, O> for TransferEnd<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode(header: Header, reader: &mut R, _: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        assert_eq!(header.kind, Kind::TransferEnd);
+
+        let session = u128::decode(reader)?;
+
+        Ok(Self {
+            header,
+            session,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<W, S> Encode<W> for TransferEnd<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.header.encode(writer)?;
+        self.session.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        buffer::byte_str, tests::verify_encode_decode, Ack, Packet, SharedImpl, INTERNAL_ERROR,
+        SUCCESS,
+    };
+
+    use super::TransferEnd;
+
+    #[test]
+    fn acks() {
+        let end = TransferEnd::<SharedImpl>::new(1, 2, 7);
+
+        let ack = end.clone().ack();
+        assert_eq!(ack.response().code(), SUCCESS);
+        assert_eq!(ack.session(), 7);
+        assert!(ack.failed().is_empty());
+
+        let nack = end.clone().nack(INTERNAL_ERROR, None);
+        assert_eq!(nack.response().code(), INTERNAL_ERROR);
+
+        let incomplete = end.incomplete(vec![byte_str(b"/data/store.log")], INTERNAL_ERROR, None);
+        assert_eq!(incomplete.response().code(), INTERNAL_ERROR);
+        assert_eq!(incomplete.failed(), &[byte_str(b"/data/store.log")]);
+    }
+
+    #[test]
+    fn encode_decode() {
+        verify_encode_decode(Packet::TransferEnd(TransferEnd::new(1, 2, 7)));
+    }
+}
@@ -0,0 +1,116 @@
+use std::io::{Read, Write};
+
+use crate::{
+    buffer::{ByteStr, Owned},
+    Ack, Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode, Response, Shared,
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct TransferEndAck<S>
+where
+    S: Shared,
+{
+    pub(crate) header: Header,
+    pub(crate) response: Response<S>,
+    pub(crate) session: u128,
+    pub(crate) failed: Vec<ByteStr<S>>,
+}
+
+impl<S> TransferEndAck<S>
+where
+    S: Shared,
+{
+    pub fn session(&self) -> u128 {
+        self.session
+    }
+
+    /// Returns the files that are missing or do not match the manifest.
+    pub fn failed(&self) -> &[ByteStr<S>] {
+        &self.failed
+    }
+}
+
+impl<R, O> PartialDecode<R, O> for TransferEndAck<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode(header: Header, reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        assert_eq!(header.kind, Kind::TransferEndAck);
+
+        let response = Response::decode_owned(reader, buffer)?;
+        let session = u128::decode(reader)?;
+        let failed = Vec::decode_owned(reader, buffer)?;
+
+        Ok(Self {
+            header,
+            response,
+            session,
+            failed,
+        })
+    }
+}
+
+impl<W, S> Encode<W> for TransferEndAck<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.header.encode(writer)?;
+        self.response.encode(writer)?;
+        self.session.encode(writer)?;
+        self.failed.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+impl<S> Ack<S> for TransferEndAck<S>
+where
+    S: Shared,
+{
+    fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn response(&self) -> Response<S> {
+        self.response.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        buffer::byte_str, tests::verify_encode_decode, Header, Kind, Packet, Response, SharedImpl,
+    };
+
+    use super::TransferEndAck;
+
+    impl TransferEndAck<SharedImpl> {
+        pub fn new(response: Response<SharedImpl>, session: u128) -> Self {
+            Self {
+                header: Header::new_test_ack(Kind::TransferEndAck),
+                response,
+                session,
+                failed: vec![],
+            }
+        }
+    }
+
+    #[test]
+    fn encode_decode() {
+        verify_encode_decode(Packet::TransferEndAck(TransferEndAck::new(
+            Response::success(),
+            7,
+        )));
+
+        let mut incomplete = TransferEndAck::new(Response::fail(1, None), 7);
+        incomplete.failed = vec![byte_str(b"/data/store.db"), byte_str(b"/data/store.log")];
+        verify_encode_decode(Packet::TransferEndAck(incomplete));
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    io::{Read, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    buffer::{Owned, Shared},
+    header::{Uuid, Version},
+    Decode, DecodeOwned, Encode, Error, Header, Kind, PartialDecode,
+};
+
+use super::Address;
+
+/// Sent from the `Tail` to the `Operator` while it transfers its data to a `Candidate`, so the
+/// operator can tell a slow transfer from a stalled one.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct TransferProgress<S>
+where
+    S: Shared,
+{
+    pub(crate) header: Header,
+    pub(crate) candidate: Address<S>,
+    pub(crate) session: u128,
+    pub(crate) sent: u64,
+    pub(crate) total: u64,
+    /// The estimated time left in milliseconds.
+    pub(crate) eta: Option<u64>,
+}
+
+impl<S> TransferProgress<S>
+where
+    S: Shared,
+{
+    pub fn new(
+        version: impl Into<Version>,
+        uuid: impl Into<Uuid>,
+        candidate: Address<S>,
+        session: u128,
+        sent: u64,
+        total: u64,
+        eta: Option<Duration>,
+    ) -> Self {
+        Self {
+            header: Header::new(
+                Kind::TransferProgress,
+                version,
+                uuid,
+                candidate.encode_len(),
+            ),
+            candidate,
+            session,
+            sent,
+            total,
+            eta: eta.map(|eta| u64::try_from(eta.as_millis()).unwrap_or(u64::MAX)),
+        }
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Returns the address of the candidate receiving the transfer.
+    pub fn candidate(&self) -> &Address<S> {
+        &self.candidate
+    }
+
+    pub fn session(&self) -> u128 {
+        self.session
+    }
+
+    /// Returns the number of bytes sent so far.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Returns the number of bytes in the whole transfer.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the estimated time until the transfer completes, if the tail has one.
+    pub fn eta(&self) -> Option<Duration> {
+        self.eta.map(Duration::from_millis)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.sent >= self.total
+    }
+}
+
+impl<R, O> PartialDecode<R, O> for TransferProgress<O::Shared>
+where
+    R: Read,
+    O: Owned,
+{
+    fn decode(header: Header, reader: &mut R, buffer: &mut O) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        assert_eq!(header.kind, Kind::TransferProgress);
+
+        let candidate = Address::decode_owned(reader, buffer)?;
+        let session = u128::decode(reader)?;
+        let sent = u64::decode(reader)?;
+        let total = u64::decode(reader)?;
+        let eta = Option::decode(reader)?;
+
+        Ok(Self {
+            header,
+            candidate,
+            session,
+            sent,
+            total,
+            eta,
+        })
+    }
+}
+
+impl<W, S> Encode<W> for TransferProgress<S>
+where
+    W: Write,
+    S: Shared,
+{
+    fn encode(&self, writer: &mut W) -> Result<(), Error> {
+        self.header.encode(writer)?;
+        self.candidate.encode(writer)?;
+        self.session.encode(writer)?;
+        self.sent.encode(writer)?;
+        self.total.encode(writer)?;
+        self.eta.encode(writer)?;
+
+        Ok(())
+    }
+}
+
+/// Tracks the [`TransferProgress`] of a candidate so the operator can time it out when no bytes
+/// have been sent for `timeout`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StallTimer {
+    timeout: Duration,
+    sent: u64,
+    since: Instant,
+}
+
+impl StallTimer {
+    /// Starts the timer at `now`, usually when the candidate was reported to the tail.
+    pub fn new(timeout: Duration, now: Instant) -> Self {
+        Self {
+            timeout,
+            sent: 0,
+            since: now,
+        }
+    }
+
+    /// Records progress received at `now`. The timer only restarts if more bytes were sent.
+    pub fn observe<S>(&mut self, progress: &TransferProgress<S>, now: Instant)
+    where
+        S: Shared,
+    {
+        if progress.sent > self.sent {
+            self.sent = progress.sent;
+            self.since = now;
+        }
+    }
+
+    /// Returns `true` if no bytes have been sent for longer than the timeout.
+    pub fn is_stalled(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.since) > self.timeout
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::{system_codec::test_address, tests::verify_encode_decode, Packet};
+
+    use super::{StallTimer, TransferProgress};
+
+    #[test]
+    fn stall_timer() {
+        let start = Instant::now();
+        let mut timer = StallTimer::new(Duration::from_secs(5), start);
+        assert!(!timer.is_stalled(start + Duration::from_secs(5)));
+        assert!(timer.is_stalled(start + Duration::from_secs(6)));
+
+        let progress = TransferProgress::new(1, 2, test_address(b"candidate"), 7, 10, 100, None);
+        timer.observe(&progress, start + Duration::from_secs(4));
+        assert!(!timer.is_stalled(start + Duration::from_secs(6)));
+
+        // the same progress again does not count.
+        timer.observe(&progress, start + Duration::from_secs(8));
+        assert!(timer.is_stalled(start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn encode_decode() {
+        let progress = TransferProgress::new(
+            1,
+            2,
+            test_address(b"candidate"),
+            7,
+            10,
+            100,
+            Some(Duration::from_millis(1500)),
+        );
+        assert_eq!(progress.eta(), Some(Duration::from_millis(1500)));
+        assert!(!progress.is_complete());
+        verify_encode_decode(Packet::TransferProgress(progress));
+
+        verify_encode_decode(Packet::TransferProgress(TransferProgress::new(
+            1,
+            2,
+            test_address(b"candidate"),
+            7,
+            100,
+            100,
+            None,
+        )));
+    }
+}